@@ -0,0 +1,36 @@
+//! The tree-walking evaluator against compiled lambdas, on a tail-recursive
+//! loop and on non-tail calls. See [`lispy::Interpreter::compile`] for the
+//! speedups measured.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lispy::Interpreter;
+
+const COUNT: &str =
+    "fun {count n acc} {if (== n 0) {acc} {count (- n 1) (+ acc n)}}";
+
+const FIB: &str =
+    "fun {fib n} {if (< n 2) {n} {+ (fib (- n 1)) (fib (- n 2))}}";
+
+fn bench(c: &mut Criterion, name: &str, definition: &str, call: &str) {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        lisp.eval(definition).unwrap();
+        let name = match compile {
+            true => format!("{} compiled", name),
+            false => name.to_string(),
+        };
+        c.bench_function(&name, |b| b.iter(|| lisp.eval(call).unwrap()));
+    }
+}
+
+fn count(c: &mut Criterion) {
+    bench(c, "count 100k", COUNT, "count 100000 0");
+}
+
+fn fib(c: &mut Criterion) {
+    bench(c, "fib 20", FIB, "fib 20");
+}
+
+criterion_group!(benches, count, fib);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use lispy::Interpreter;
+
+fn fold(c: &mut Criterion) {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {xs} (range 100000)").unwrap();
+    c.bench_function("fold 100k", |b| b.iter(|| {
+        lisp.eval("fold + 0 xs").unwrap()
+    }));
+}
+
+criterion_group!(benches, fold);
+criterion_main!(benches);
@@ -1,30 +1,51 @@
-use std::convert::TryInto;
+use std::{convert::TryInto, fmt, rc::Rc};
 use super::{
+    debugger,
     env::EnvObj,
-    eval_error::{EvalError, EvalResult},
-    function::Function,
+    eval_error::{ErrorValue, EvalError, EvalResult},
+    function::{Function, Lambda},
+    map::{Key, Map},
+    number::Number,
+    reference::Ref,
     qexpr::{QExpr, pprint},
+    span::Span,
     transformers::sequence,
 };
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum AST {
-    Number(i128),
-    Symbol(String),
-    Literal(String),
-    SExpr(Vec<AST>),
+    Bool(bool),
+    Number(Number),
+    Symbol(Rc<str>, Span),
+    Literal(Rc<str>),
+    SExpr(Rc<[AST]>, Span),
     QExpr(QExpr),
+    Map(Map),
+    Ref(Ref),
     Function(Function),
+    Error(Rc<ErrorValue>),
 }
 
 impl Default for AST {
     fn default() -> Self {
-        AST::SExpr(vec![])
+        AST::SExpr(Rc::new([]), Span::default())
+    }
+}
+
+impl From<bool> for AST {
+    fn from(x: bool) -> Self {
+        AST::Bool(x)
     }
 }
 
 impl From<i128> for AST {
     fn from(x: i128) -> Self {
+        AST::Number(Number::Int(x))
+    }
+}
+
+impl From<Number> for AST {
+    fn from(x: Number) -> Self {
         AST::Number(x)
     }
 }
@@ -35,55 +56,169 @@ impl From<QExpr> for AST {
     }
 }
 
+impl From<Map> for AST {
+    fn from(x: Map) -> Self {
+        AST::Map(x)
+    }
+}
+
+impl From<String> for AST {
+    fn from(x: String) -> Self {
+        AST::Literal(x.into())
+    }
+}
+
+impl TryInto<String> for AST {
+    type Error = EvalError;
+
+    fn try_into(self) -> Result<String, EvalError> {
+        self.literal()
+    }
+}
+
 impl TryInto<i128> for AST {
     type Error = EvalError;
 
-    fn try_into(self) -> Result<i128, Self::Error> {
+    fn try_into(self) -> Result<i128, EvalError> {
+        self.integer()
+    }
+}
+
+impl TryInto<Number> for AST {
+    type Error = EvalError;
+
+    fn try_into(self) -> Result<Number, EvalError> {
         self.number()
     }
 }
 
+impl TryInto<Map> for AST {
+    type Error = EvalError;
+
+    fn try_into(self) -> Result<Map, EvalError> {
+        self.map()
+    }
+}
+
 impl TryInto<QExpr> for AST {
     type Error = EvalError;
 
-    fn try_into(self) -> Result<QExpr, Self::Error> {
+    fn try_into(self) -> Result<QExpr, EvalError> {
         self.qexpr()
     }
 }
 
+/// What is left to do with an expression once everything except its tail
+/// position has been evaluated.
+pub enum Step {
+    Done(AST),
+    Eval(AST),
+    Apply(Rc<Lambda>, Vec<AST>),
+}
+
+impl Step {
+    pub fn run(self, env: EnvObj) -> EvalResult {
+        match self {
+            Step::Done(ast) => Ok(ast),
+            Step::Eval(ast) => ast.eval(env),
+            Step::Apply(fun, args) => fun.apply(env, args),
+        }
+    }
+}
+
 impl AST {
     pub fn eval(self, env: EnvObj) -> EvalResult {
+        match self.eval_tail(env)? {
+            Step::Apply(fun, args) => fun.apply(env, args),
+            step => step.run(env),
+        }
+    }
+
+    /// Evaluates until the result is either a value or a lambda application,
+    /// so that the caller can run the latter without growing the stack.
+    pub fn eval_tail(self, env: EnvObj) -> EvalResult<Step> {
+        let mut ast = self;
+        loop {
+            match ast.step(env)? {
+                Step::Eval(next) => ast = next,
+                step => return Ok(step),
+            }
+        }
+    }
+
+    fn step(self, env: EnvObj) -> EvalResult<Step> {
+        let call = matches!(&self, AST::SExpr(exprs, _) if !exprs.is_empty());
+        if call && env.debugging() {
+            debugger::on_eval(env, &self);
+        }
         match self {
-            AST::Symbol(var) => env.get(var),
-            AST::SExpr(mut expr) if expr.len() == 1 =>
-                expr.pop().unwrap().eval(env),
-            AST::SExpr(exprs) if !exprs.is_empty() => {
-                let mut exprs = sequence(exprs, |x| x.eval(env))?.into_iter();
-                let fun = exprs.next().unwrap().function()?;
-                fun.call(env, exprs)
+            AST::Symbol(var, span) =>
+                env.get(&var).map(Step::Done).map_err(|e| e.at(&span)),
+            AST::SExpr(expr, span) if expr.len() == 1 => match &expr[0] {
+                AST::Symbol(var, at) => env.get(var).map(Step::Done)
+                    .map_err(|e| e.at(at).at(&span)),
+                ast => Ok(Step::Eval(ast.clone())),
+            },
+            AST::SExpr(exprs, span) if !exprs.is_empty() => {
+                let mut call = || {
+                    let mut exprs = exprs.iter().cloned();
+                    let fun = exprs.next().unwrap().eval(env)?.function()?;
+                    match fun {
+                        Function::Special(..) | Function::Macro(_) => {
+                            let args = exprs.collect::<Vec<_>>();
+                            fun.step(env, args.into_iter())
+                        },
+                        fun => {
+                            let args = sequence(exprs, |x| x.eval(env))?;
+                            fun.step(env, args.into_iter())
+                        },
+                    }
+                };
+                call().map_err(|e| e.at(&span))
             },
-            ast => Ok(ast),
+            ast => Ok(Step::Done(ast)),
+        }
+    }
+
+    /// Whether a condition holds: every value counts as true except `#f` and
+    /// nil, the empty Q-expression.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            AST::Bool(x) => *x,
+            AST::QExpr(xs) => !xs.is_empty(),
+            _ => true,
         }
     }
 
     pub fn typ(&self) -> &'static str {
         match self {
+            AST::Bool(_) => "bool",
             AST::Number(_) => "number",
-            AST::Symbol(_) => "symbol",
+            AST::Symbol(..) => "symbol",
             AST::Literal(_) => "string",
-            AST::SExpr(_) => "S-expr",
+            AST::SExpr(..) => "S-expr",
             AST::QExpr(_) => "Q-expr",
+            AST::Map(_) => "map",
+            AST::Ref(_) => "ref",
             AST::Function(_) => "function",
+            AST::Error(_) => "error",
         }
     }
 
-    pub fn number(self) -> EvalResult<i128> {
+    pub fn number(self) -> EvalResult<Number> {
         match self {
             AST::Number(x) => Ok(x),
             ast => Err(EvalError::NotA("number", ast)),
         }
     }
 
+    pub fn integer(self) -> EvalResult<i128> {
+        match self {
+            AST::Number(Number::Int(x)) => Ok(x),
+            ast => Err(EvalError::NotA("integer", ast)),
+        }
+    }
+
     pub fn qexpr(self) -> EvalResult<QExpr> {
         match self {
             AST::QExpr(xs) => Ok(xs),
@@ -93,40 +228,77 @@ impl AST {
 
     pub fn symbol(self) -> EvalResult<String> {
         match self {
-            AST::Symbol(sym) => Ok(sym),
+            AST::Symbol(sym, _) => Ok(sym.to_string()),
             ast => Err(EvalError::NotA("symbol", ast)),
         }
     }
 
     pub fn literal(self) -> EvalResult<String> {
         match self {
-            AST::Literal(lit) => Ok(lit),
+            AST::Literal(lit) => Ok(lit.to_string()),
             ast => Err(EvalError::NotA("string", ast)),
         }
     }
 
-    fn function(self) -> EvalResult<Function> {
+    pub fn map(self) -> EvalResult<Map> {
+        match self {
+            AST::Map(map) => Ok(map),
+            ast => Err(EvalError::NotA("map", ast)),
+        }
+    }
+
+    pub fn reference(self) -> EvalResult<Ref> {
+        match self {
+            AST::Ref(cell) => Ok(cell),
+            ast => Err(EvalError::NotA("ref", ast)),
+        }
+    }
+
+    pub fn key(self) -> EvalResult<Key> {
+        match self {
+            AST::Number(Number::Float(x)) if x.is_nan() =>
+                Err(EvalError::Message("NaN cannot be a map key")),
+            AST::Number(x) => Ok(Key::Number(x)),
+            AST::Symbol(x, _) => Ok(Key::Symbol(x)),
+            AST::Literal(x) => Ok(Key::Literal(x)),
+            ast => Err(EvalError::NotA("map key", ast)),
+        }
+    }
+
+    pub fn function(self) -> EvalResult<Function> {
         match self {
             AST::Function(fun) => Ok(fun),
             ast => Err(EvalError::NotA("function", ast)),
         }
     }
+
+    pub fn error(self) -> EvalResult<ErrorValue> {
+        match self {
+            AST::Error(err) => Ok(Rc::unwrap_or_clone(err)),
+            ast => Err(EvalError::NotA("error", ast)),
+        }
+    }
 }
 
-impl ToString for AST {
-    fn to_string(&self) -> String {
+impl fmt::Display for AST {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            AST::Number(num) => num.to_string(),
-            AST::Symbol(sym) => sym.clone(),
-            AST::Literal(string) => format!("\"{}\"", escaped(string)),
-            AST::SExpr(asts) => pprint("(", asts, ")"),
-            AST::QExpr(asts) => asts.to_string(),
-            AST::Function(fun) => fun.to_string(),
+            AST::Bool(true) => f.write_str("#t"),
+            AST::Bool(false) => f.write_str("#f"),
+            AST::Number(num) => num.fmt(f),
+            AST::Symbol(sym, _) => f.write_str(sym),
+            AST::Literal(string) => write!(f, "\"{}\"", escaped(string)),
+            AST::SExpr(asts, _) => f.write_str(&pprint("(", asts.iter(), ")")),
+            AST::QExpr(asts) => asts.fmt(f),
+            AST::Map(map) => map.fmt(f),
+            AST::Ref(cell) => cell.fmt(f),
+            AST::Function(fun) => fun.fmt(f),
+            AST::Error(err) => err.fmt(f),
         }
     }
 }
 
-fn escaped(string: &String) -> String {
+fn escaped(string: &str) -> String {
     string
         .replace("\n", "\\n")
         .replace("\t", "\\t")
@@ -0,0 +1,397 @@
+//! Lowers the body of a lambda to bytecode for the stack machine in
+//! [`crate::vm`].
+//!
+//! Parameters and captured variables are resolved to numbered slots once,
+//! and `if`, `and`, `or` and `cond` become jumps, so a call no longer builds
+//! a scope or looks anything up by name but the globals it uses. The forms
+//! this compiler does not know, such as other special forms and macro calls,
+//! are kept as trees and handed back to [`AST::eval`] when reached. They see
+//! the slots through an environment like any other scope.
+//!
+//! Which names are special forms is decided when the lambda is compiled:
+//! rebinding `if` afterwards does not change the compiled code.
+//!
+//! Calls take most of the time left: each collects its arguments into a
+//! vector and runs on a stack of its own. That keeps compiled code at about
+//! six times the speed of the tree walker on a counting loop, and five and a
+//! half on `fib`, short of an order of magnitude, see `benches/count.rs`.
+
+use std::{cell::RefCell, rc::Rc};
+use super::{
+    ast::AST, env::Env, function::Function, qexpr::QExpr, span::Span,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    /// Pushes a constant.
+    Const(usize),
+    /// Pushes the value of a slot.
+    Local(usize),
+    /// Pushes the value of a name that is not a slot.
+    Global(usize),
+    /// Calls the function below the `n` topmost values with them.
+    Call(usize),
+    /// Like `Call`, then returns whatever that call would run next.
+    TailCall(usize),
+    Jump(usize),
+    /// Pops a value and jumps if it is false.
+    JumpIfFalse(usize),
+    /// Jumps if the top value is false, pops it otherwise.
+    JumpIfFalseOrPop(usize),
+    /// Jumps if the top value is true, pops it otherwise.
+    JumpIfTrueOrPop(usize),
+    Pop,
+    Return,
+    /// Calls the global operator with the two topmost values, doing the work
+    /// inline when it is still the builtin constant it was compiled from and
+    /// both values are small integers.
+    Prim(Prim, usize, usize),
+    /// Pushes the value of a constant tree, evaluated by the interpreter.
+    Eval(usize),
+    /// Returns what is left to do after evaluating a constant tree.
+    TailEval(usize),
+}
+
+/// The builtins worth running without a call on small integers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prim {
+    Add,
+    Sub,
+    Mul,
+    Less,
+    Eq,
+}
+
+impl Prim {
+    fn named(name: &str) -> Option<Prim> {
+        match name {
+            "+" => Some(Prim::Add),
+            "-" => Some(Prim::Sub),
+            "*" => Some(Prim::Mul),
+            "<" => Some(Prim::Less),
+            "==" => Some(Prim::Eq),
+            _ => None,
+        }
+    }
+
+    /// What the builtin gives for `x` and `y`, unless it overflows.
+    pub fn ints(self, x: i128, y: i128) -> Option<AST> {
+        match self {
+            Prim::Add => x.checked_add(y).map(AST::from),
+            Prim::Sub => x.checked_sub(y).map(AST::from),
+            Prim::Mul => x.checked_mul(y).map(AST::from),
+            Prim::Less => Some(AST::from(x < y)),
+            Prim::Eq => Some(AST::from(x == y)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    /// The S-expression each op comes from, for error locations.
+    pub spans: Vec<Span>,
+    pub constants: Vec<AST>,
+    pub globals: Vec<Rc<str>>,
+    /// The last value seen of each global, with the generation of the
+    /// environment it was seen in, see [`Env::generation`].
+    pub cache: RefCell<Vec<Option<(usize, AST)>>>,
+    /// The names of the slots: the parameters, the vararg if any, and the
+    /// captured variables.
+    pub locals: Vec<String>,
+    /// How many of the slots hold the arguments.
+    pub params: usize,
+    /// About how many values the code keeps on the stack at most, reserved
+    /// before it runs.
+    pub stack: usize,
+}
+
+impl Chunk {
+    /// The value of the slot called `name` among `slots`.
+    pub fn local(&self, name: &str, slots: &[AST]) -> Option<AST> {
+        let slot = self.locals.iter().position(|local| local == name)?;
+        slots.get(slot).cloned()
+    }
+}
+
+/// Compiles `code`, the body of a lambda, with `locals` as its slots, the
+/// first `params` of them being the arguments. The global bindings of `env`
+/// tell the special forms apart from functions.
+pub fn compile(env: &dyn Env, locals: Vec<String>, params: usize, code: &AST)
+    -> Chunk
+{
+    let mut compiler = Compiler {
+        env,
+        chunk: Chunk { locals, params, ..Chunk::default() },
+        span: Span::default(),
+        height: 0,
+    };
+    compiler.expr(code, true);
+    let mut chunk = compiler.chunk;
+    chunk.cache = RefCell::new(vec![None; chunk.globals.len()]);
+    chunk
+}
+
+struct Compiler<'a> {
+    env: &'a dyn Env,
+    chunk: Chunk,
+    span: Span,
+    /// The height of the stack after the last op, counting both branches
+    /// of a jump as if they ran in turn.
+    height: usize,
+}
+
+impl<'a> Compiler<'a> {
+    /// Emits the code of `ast`. In tail position that code returns.
+    fn expr(&mut self, ast: &AST, tail: bool) {
+        match ast {
+            AST::Symbol(sym, span) => self.symbol(sym, span),
+            AST::SExpr(exprs, span) if exprs.len() == 1 => {
+                let outer = std::mem::replace(&mut self.span, span.clone());
+                match &exprs[0] {
+                    AST::Symbol(sym, span) => self.symbol(sym, span),
+                    expr => {
+                        self.expr(expr, tail);
+                        self.span = outer;
+                        return;
+                    },
+                }
+                self.span = outer;
+            },
+            AST::SExpr(exprs, span) if !exprs.is_empty() => {
+                let outer = std::mem::replace(&mut self.span, span.clone());
+                self.form(ast, exprs, tail);
+                self.span = outer;
+                return;
+            },
+            ast => self.constant(ast.clone()),
+        }
+        if tail {
+            self.emit(Op::Return);
+        }
+    }
+
+    fn form(&mut self, ast: &AST, exprs: &[AST], tail: bool) {
+        let head = match &exprs[0] {
+            AST::Symbol(head, _) if self.slot(head).is_none() =>
+                self.env.get(head).ok().map(|value| (&**head, value)),
+            _ => None,
+        };
+        let args = &exprs[1..];
+        match head {
+            Some(("if", AST::Function(Function::Tail(..))))
+                if self.if_form(args, tail) => {},
+            Some(("and", AST::Function(Function::Special(..)))) =>
+                self.junction(args, true, tail),
+            Some(("or", AST::Function(Function::Special(..)))) =>
+                self.junction(args, false, tail),
+            Some(("cond", AST::Function(Function::Special(..))))
+                if self.cond(args, tail) => {},
+            Some((_, AST::Function(Function::Special(..) | Function::Macro(_))))
+                => {
+                let index = self.add_constant(ast.clone());
+                self.emit(if tail { Op::TailEval(index) }
+                          else { Op::Eval(index) });
+            },
+            Some((name, fun @ AST::Function(Function::Builtin(..))))
+                if args.len() == 2 && Prim::named(name).is_some() => {
+                for expr in args {
+                    self.expr(expr, false);
+                }
+                let global = self.global(name);
+                let builtin = self.add_constant(fun);
+                let prim = Prim::named(name).unwrap();
+                self.emit(Op::Prim(prim, global, builtin));
+                if tail {
+                    self.emit(Op::Return);
+                }
+            },
+            _ => {
+                for expr in exprs {
+                    self.expr(expr, false);
+                }
+                self.emit(if tail { Op::TailCall(args.len()) }
+                          else { Op::Call(args.len()) });
+            },
+        }
+    }
+
+    /// `(if c {then} {else})` with both branches written out. Anything else
+    /// is left to the `if` builtin.
+    fn if_form(&mut self, args: &[AST], tail: bool) -> bool {
+        let (test, then, otherwise) = match args {
+            [test, AST::QExpr(then), AST::QExpr(otherwise)] =>
+                (test, then.clone().sexpr(), otherwise.clone().sexpr()),
+            _ => return false,
+        };
+        self.expr(test, false);
+        let to_else = self.emit(Op::JumpIfFalse(0));
+        self.expr(&then, tail);
+        let to_end = if tail { None } else { Some(self.emit(Op::Jump(0))) };
+        self.patch(to_else);
+        self.expr(&otherwise, tail);
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+        true
+    }
+
+    /// `and` if `all`, `or` otherwise: the first argument that settles the
+    /// result is returned, the others are not evaluated.
+    fn junction(&mut self, args: &[AST], all: bool, tail: bool) {
+        let (last, init) = match args.split_last() {
+            Some(split) => split,
+            None => return self.expr(&AST::Bool(all), tail),
+        };
+        let mut exits = vec![];
+        for arg in init {
+            self.expr(arg, false);
+            exits.push(self.emit(match all {
+                true => Op::JumpIfFalseOrPop(0),
+                false => Op::JumpIfTrueOrPop(0),
+            }));
+        }
+        self.expr(last, tail);
+        for exit in exits {
+            self.patch(exit);
+        }
+        if tail && !init.is_empty() {
+            self.emit(Op::Return);
+        }
+    }
+
+    /// `cond` whose clauses are all written out, see [`crate::env`].
+    fn cond(&mut self, clauses: &[AST], tail: bool) -> bool {
+        let clauses = clauses.iter().map(|clause| match clause {
+            AST::QExpr(clause) if !clause.is_empty() =>
+                Some(clause.iter().cloned().collect::<Vec<_>>()),
+            _ => None,
+        });
+        let clauses = match clauses.collect::<Option<Vec<_>>>() {
+            Some(clauses) => clauses,
+            None => return false,
+        };
+        let mut exits = vec![];
+        for clause in clauses {
+            let (test, body) = clause.split_first().unwrap();
+            let is_else = matches!(test, AST::Symbol(s, _) if &**s == "else");
+            if body.is_empty() && is_else {
+                self.expr(&AST::Bool(true), tail);
+                return self.finish(exits, tail);
+            } else if body.is_empty() {
+                self.expr(test, false);
+                exits.push(self.emit(Op::JumpIfTrueOrPop(0)));
+                continue;
+            }
+            let next = match is_else {
+                true => None,
+                false => {
+                    self.expr(test, false);
+                    Some(self.emit(Op::JumpIfFalse(0)))
+                },
+            };
+            self.body(body, tail);
+            if !tail {
+                exits.push(self.emit(Op::Jump(0)));
+            }
+            match next {
+                Some(next) => self.patch(next),
+                None => return self.finish(exits, tail),
+            }
+        }
+        self.expr(&QExpr::from(vec![]).into(), tail);
+        self.finish(exits, tail)
+    }
+
+    fn finish(&mut self, exits: Vec<usize>, tail: bool) -> bool {
+        let returns = tail && !exits.is_empty();
+        for exit in exits {
+            self.patch(exit);
+        }
+        if returns {
+            self.emit(Op::Return);
+        }
+        true
+    }
+
+    /// Evaluates every expression in turn, giving the value of the last.
+    fn body(&mut self, body: &[AST], tail: bool) {
+        let (last, init) = body.split_last().unwrap();
+        for expr in init {
+            self.expr(expr, false);
+            self.emit(Op::Pop);
+        }
+        self.expr(last, tail);
+    }
+
+    /// Emits the lookup of `sym`, failing at `span` if that is known.
+    fn symbol(&mut self, sym: &Rc<str>, span: &Span) {
+        let outer = match span.is_known() {
+            true => Some(std::mem::replace(&mut self.span, span.clone())),
+            false => None,
+        };
+        match self.slot(sym) {
+            Some(slot) => self.emit(Op::Local(slot)),
+            None => {
+                let index = self.global(sym);
+                self.emit(Op::Global(index))
+            },
+        };
+        if let Some(outer) = outer {
+            self.span = outer;
+        }
+    }
+
+    /// The index of the global called `name`, added if it is new.
+    fn global(&mut self, name: &str) -> usize {
+        self.chunk.globals.iter()
+            .position(|global| &**global == name)
+            .unwrap_or_else(|| {
+                self.chunk.globals.push(name.into());
+                self.chunk.globals.len() - 1
+            })
+    }
+
+    fn slot(&self, sym: &str) -> Option<usize> {
+        self.chunk.locals.iter().position(|name| name == sym)
+    }
+
+    fn constant(&mut self, ast: AST) {
+        let index = self.add_constant(ast);
+        self.emit(Op::Const(index));
+    }
+
+    fn add_constant(&mut self, ast: AST) -> usize {
+        self.chunk.constants.push(ast);
+        self.chunk.constants.len() - 1
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let (pops, pushes) = match op {
+            Op::Const(_) | Op::Local(_) | Op::Global(_) | Op::Eval(_) => (0, 1),
+            Op::Call(argc) => (argc + 1, 1),
+            Op::TailCall(argc) => (argc + 1, 0),
+            Op::Prim(..) => (2, 1),
+            Op::JumpIfFalse(_) | Op::JumpIfFalseOrPop(_)
+                | Op::JumpIfTrueOrPop(_) | Op::Pop | Op::Return => (1, 0),
+            Op::Jump(_) | Op::TailEval(_) => (0, 0),
+        };
+        self.height = self.height.saturating_sub(pops) + pushes;
+        self.chunk.stack = self.chunk.stack.max(self.height);
+        self.chunk.ops.push(op);
+        self.chunk.spans.push(self.span.clone());
+        self.chunk.ops.len() - 1
+    }
+
+    /// Makes the jump at `at` land on the next op.
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.ops.len();
+        self.chunk.ops[at] = match self.chunk.ops[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpIfFalseOrPop(_) => Op::JumpIfFalseOrPop(target),
+            Op::JumpIfTrueOrPop(_) => Op::JumpIfTrueOrPop(target),
+            op => op,
+        };
+    }
+}
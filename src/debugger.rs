@@ -0,0 +1,233 @@
+//! A debugger for the REPL: breakpoints on lambdas, stepping through the
+//! S-expressions being evaluated, and a look into the scope it stopped in.
+//!
+//! The evaluator tells the debugger attached to the global environment
+//! about every lambda it enters and every S-expression it is about to
+//! evaluate. Once stopped, the debugger reads commands from its [`Console`]:
+//!
+//! - `:step` runs up to the next S-expression, `:next` up to the next one
+//!   that is not inside a lambda called from here, and `:continue` up to
+//!   the next breakpoint;
+//! - `:locals` shows the local bindings, `:bt` the lambdas being run;
+//! - `:break name` stops whenever a lambda first bound to `name` is called;
+//! - anything else is evaluated where the evaluation stopped.
+//!
+//! Compiled lambdas are run by the tree-walking evaluator while a debugger
+//! has something to do, so that each of their steps can be seen.
+
+use std::collections::BTreeSet;
+use super::{
+    ast::AST,
+    env::EnvObj,
+    eval_error::Frame,
+    macros,
+    parser::parse,
+};
+
+/// Where a debugger reads commands and shows what it found.
+pub trait Console {
+    /// Reads a line, or gives `None` at the end of input.
+    fn read_line(&mut self, prompt: &str) -> Option<String>;
+    fn print(&mut self, text: &str);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Run,
+    Step,
+    /// Stepping over the lambdas called deeper than this many frames.
+    Next(usize),
+}
+
+enum Command {
+    Break(String),
+    Step,
+    Next,
+    Continue,
+    Locals,
+    Backtrace,
+}
+
+impl Command {
+    /// The command written on `line`, `None` if it is not a command, or an
+    /// error message if it is not a known one.
+    fn parse(line: &str) -> Option<Result<Command, String>> {
+        let mut words = line.strip_prefix(':')?.split_whitespace();
+        let command = match (words.next(), words.next(), words.next()) {
+            (Some("break"), Some(name), None) => Command::Break(name.into()),
+            (Some("step"), None, _) => Command::Step,
+            (Some("next"), None, _) => Command::Next,
+            (Some("continue"), None, _) => Command::Continue,
+            (Some("locals"), None, _) => Command::Locals,
+            (Some("bt"), None, _) => Command::Backtrace,
+            _ => return Some(Err(format!("unknown command: {}", line))),
+        };
+        Some(Ok(command))
+    }
+}
+
+pub struct Debugger {
+    console: Box<dyn Console>,
+    breakpoints: BTreeSet<String>,
+    mode: Mode,
+    frames: Vec<Frame>,
+}
+
+impl Debugger {
+    pub fn new(console: Box<dyn Console>) -> Self {
+        Self {
+            console,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Run,
+            frames: vec![],
+        }
+    }
+
+    /// Runs a command given while the evaluation is not stopped, where only
+    /// `:break` and `:step` make sense. Tells whether `line` was a command.
+    pub fn command(&mut self, line: &str) -> bool {
+        match Command::parse(line.trim()) {
+            Some(Ok(Command::Break(name))) => self.breakpoints.insert(name),
+            Some(Ok(Command::Step)) => {
+                self.mode = Mode::Step;
+                true
+            },
+            Some(Ok(_)) => {
+                self.console.print("not stopped in the debugger");
+                true
+            },
+            Some(Err(message)) => {
+                self.console.print(&message);
+                true
+            },
+            None => return false,
+        };
+        true
+    }
+
+    /// Forgets the lambdas being run and stops stepping, once a top-level
+    /// evaluation is over.
+    pub fn finish(&mut self) {
+        self.frames.clear();
+        self.mode = Mode::Run;
+    }
+
+    /// Whether there is a breakpoint or the debugger is stepping. The
+    /// evaluator only tells it what it does then, see
+    /// [`crate::env::Env::debugging`].
+    pub fn is_armed(&self) -> bool {
+        self.mode != Mode::Run || !self.breakpoints.is_empty()
+    }
+
+    fn enter(&mut self, env: EnvObj, frame: Frame) {
+        let name = frame.name.clone();
+        self.frames.push(frame);
+        if let Some(name) = name.filter(|name| self.breakpoints.contains(name))
+        {
+            let call = self.frames.last().unwrap().to_string();
+            self.console.print(&format!("breakpoint {}:\n{}", name, call));
+            self.stop(env);
+        }
+    }
+
+    fn eval(&mut self, env: EnvObj, ast: &AST) {
+        let stop = match self.mode {
+            Mode::Run => false,
+            Mode::Step => true,
+            Mode::Next(depth) => self.frames.len() <= depth,
+        };
+        if stop {
+            let location = match ast {
+                AST::SExpr(_, span) if span.is_known() =>
+                    format!("{}: ", span),
+                _ => String::new(),
+            };
+            self.console.print(&format!("{}{}", location, ast));
+            self.stop(env);
+        }
+    }
+
+    /// Reads commands until one resumes the evaluation.
+    fn stop(&mut self, env: EnvObj) {
+        while let Some(line) = self.console.read_line("debug> ") {
+            let line = line.trim();
+            match Command::parse(line) {
+                Some(Ok(Command::Break(name))) => {
+                    self.breakpoints.insert(name);
+                },
+                Some(Ok(Command::Step)) => return self.resume(Mode::Step),
+                Some(Ok(Command::Next)) =>
+                    return self.resume(Mode::Next(self.frames.len())),
+                Some(Ok(Command::Continue)) => return self.resume(Mode::Run),
+                Some(Ok(Command::Locals)) => self.locals(env),
+                Some(Ok(Command::Backtrace)) => self.backtrace(),
+                Some(Err(message)) => self.console.print(&message),
+                None if line.is_empty() => {},
+                None => {
+                    let result = parse(line)
+                        .map_err(|err| err.to_string())
+                        .and_then(|tree| macros::expand(tree, env)
+                            .and_then(|tree| tree.eval(env))
+                            .map_err(|err| err.to_string()));
+                    match result {
+                        Ok(value) => self.console.print(&value.to_string()),
+                        Err(message) => self.console.print(&message),
+                    }
+                },
+            }
+        }
+        self.resume(Mode::Run)
+    }
+
+    fn resume(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    fn locals(&mut self, env: EnvObj) {
+        let locals = env.locals();
+        if locals.is_empty() {
+            self.console.print("no local bindings");
+        }
+        for (name, value) in locals {
+            self.console.print(&format!("{} = {}", name, value));
+        }
+    }
+
+    fn backtrace(&mut self) {
+        if self.frames.is_empty() {
+            self.console.print("at top level");
+        }
+        for frame in self.frames.iter().rev() {
+            self.console.print(&frame.to_string());
+        }
+    }
+}
+
+/// Tells the debugger of `env`, if any, that `frame` was entered, `env`
+/// being its scope.
+pub fn on_enter(env: EnvObj, frame: Frame) {
+    with_debugger(env, |debugger, env| debugger.enter(env, frame));
+}
+
+/// Tells the debugger of `env` that the innermost frame was left.
+pub fn on_leave(env: EnvObj) {
+    with_debugger(env, |debugger, _| { debugger.frames.pop(); });
+}
+
+/// Tells the debugger of `env` that `ast` is about to be evaluated there.
+pub fn on_eval(env: EnvObj, ast: &AST) {
+    with_debugger(env, |debugger, env| debugger.eval(env, ast));
+}
+
+/// The debugger is taken out of `env` while it runs, so that what it
+/// evaluates is not debugged in turn.
+fn with_debugger(env: EnvObj, f: impl FnOnce(&mut Debugger, EnvObj)) {
+    let mut debugger = match env.debugger().and_then(Option::take) {
+        Some(debugger) => debugger,
+        None => return,
+    };
+    f(&mut debugger, env);
+    if let Some(slot) = env.debugger() {
+        *slot = Some(debugger);
+    }
+}
@@ -1,83 +1,379 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
+use std::rc::Rc;
 use std::fs;
-use super::parser::parse;
-use super::{ast::*, function::*, eval_error::*};
+use super::parser::{doc_comments, forms, parse_at};
+use super::{
+    ast::*,
+    debugger::Debugger,
+    eval_error::*,
+    function::*,
+    heap::Heap,
+    macros,
+    profiler::Profiler,
+    qexpr::QExpr,
+    span::Span,
+};
+
+mod assertions;
+mod lists;
+mod maps;
+mod profile;
+mod refs;
+mod strings;
 
 pub type EnvObj<'a> = &'a mut dyn Env;
 
 pub trait Env {
-    fn get(&self, key: String) -> EvalResult<AST>;
+    fn get(&self, key: &str) -> EvalResult<AST>;
+    fn local(&self, key: &str) -> Option<AST>;
+    fn global(&mut self) -> EnvObj<'_>;
     fn define(&mut self, bindings: BindingsVec);
     fn assign(&mut self, bindings: BindingsVec);
+
+    /// Tells whether new lambdas are compiled to bytecode.
+    fn compiling(&self) -> bool {
+        false
+    }
+
+    /// A number that changes whenever a global binding does, if the
+    /// environment keeps one, so that global values can be cached.
+    fn generation(&self) -> Option<usize> {
+        None
+    }
+
+    /// Where the cells made by `ref` are tracked, if anywhere.
+    fn heap(&mut self) -> Option<&mut Heap> {
+        None
+    }
+
+    /// Where the debugger attached to this environment is kept, if anywhere.
+    fn debugger(&mut self) -> Option<&mut Option<Debugger>> {
+        None
+    }
+
+    /// Tells whether a debugger is attached and armed, see
+    /// [`Debugger::is_armed`].
+    fn debugging(&self) -> bool {
+        false
+    }
+
+    /// How deeply the lambda calls being run nest, if counted, see
+    /// `MAX_DEPTH`.
+    fn depth(&mut self) -> Option<&mut usize> {
+        None
+    }
+
+    /// Where the profile being taken in this environment is kept, if
+    /// anywhere, see [`Profiler`].
+    fn profiler(&mut self) -> Option<&mut Option<Profiler>> {
+        None
+    }
+
+    /// Where `deftest` collects the tests of a file as they are defined,
+    /// with their names, if anywhere.
+    fn tests(&mut self) -> Option<&mut Vec<(String, QExpr)>> {
+        None
+    }
+
+    /// Where `load` records the doc comments of the files it loads, if
+    /// anywhere.
+    fn docs(&mut self) -> Option<&mut Docs> {
+        None
+    }
+
+    /// The values this environment keeps alive, which the garbage
+    /// collector takes as roots: its variables and those of its parents,
+    /// and the tests.
+    fn roots(&self) -> Vec<AST> {
+        vec![]
+    }
+
+    /// The bindings `local` finds, sorted by name.
+    fn locals(&self) -> BindingsVec {
+        vec![]
+    }
+
+    /// Every binding `get` finds, sorted by name.
+    fn bindings(&self) -> BindingsVec;
 }
 
+/// The doc comments of files by name, see [`doc_comments`].
+pub type Docs = HashMap<String, BTreeMap<u32, Vec<String>>>;
+
+/// The standard library written in Lisp itself.
+pub const PRELUDE: &str = include_str!("../prelude.lispy");
+
+/// The file the spans of the prelude point to.
+pub const PRELUDE_FILE: &str = "prelude.lispy";
+
 pub fn load<P>(env: EnvObj, file: P) -> EvalResult where P: AsRef<Path> {
-    let content = fs::read_to_string(file)?;
-    for line in content.lines() {
-        match parse(line) {
-            Ok(expr) => if let Err(err) = expr.eval(env) {
-                println!("{}", err.to_string());
-            },
-            Err(err) => println!("{}", err),
-        }
+    let content = fs::read_to_string(&file)?;
+    let file = file.as_ref().display().to_string();
+    if let Some(docs) = env.docs() {
+        docs.insert(file.clone(), doc_comments(&content));
+    }
+    run(env, &content, Span::start(Some(file.into())))
+}
+
+/// Evaluates the top-level forms of `source` one by one, up to the first
+/// that fails. Garbage is collected between them if due.
+pub fn run(env: EnvObj, source: &str, start: Span) -> EvalResult {
+    for (line, form) in forms(source) {
+        let expr = parse_at(form, start.offset(line, 1))?;
+        macros::expand(expr, env)?.eval(env)?;
+        collect_garbage(env, None);
     }
     Ok(AST::default())
 }
 
+/// Runs a collection if one is due, with [`Env::roots`] and `value` as
+/// roots, see [`crate::heap`].
+pub fn collect_garbage(env: EnvObj, value: Option<&AST>) {
+    if env.heap().is_some_and(|heap| heap.is_due()) {
+        let roots = env.roots();
+        if let Some(heap) = env.heap() {
+            heap.collect(roots.iter().chain(value));
+        }
+    }
+}
+
 pub type Bindings = HashMap<String, AST>;
-type BindingsVec = Vec<(String, AST)>;
+pub type BindingsVec = Vec<(String, AST)>;
+
+/// `outer` with the bindings of `inner` in place of those of the same name,
+/// sorted by name.
+pub fn shadow(outer: BindingsVec,
+              inner: impl IntoIterator<Item = (String, AST)>) -> BindingsVec {
+    let mut bindings = outer.into_iter().collect::<Bindings>();
+    bindings.extend(inner);
+    let mut bindings = bindings.into_iter().collect::<BindingsVec>();
+    bindings.sort_by(|(x, _), (y, _)| x.cmp(y));
+    bindings
+}
 
 pub struct Global {
     bindings: Bindings,
+    compile: bool,
+    generation: usize,
+    heap: Heap,
+    depth: usize,
+    debugger: Option<Debugger>,
+    profiler: Option<Profiler>,
+    tests: Vec<(String, QExpr)>,
+    docs: Docs,
+}
+
+impl Global {
+    /// The builtins together with the definitions from [`PRELUDE`].
+    pub fn with_prelude() -> Self {
+        let mut global = Self::default();
+        let start = Span::start(Some(PRELUDE_FILE.into()));
+        run(&mut global, PRELUDE, start).expect("prelude evaluates");
+        global
+    }
+
+    /// Makes the lambdas created from now on run as bytecode, see
+    /// [`crate::compiler`].
+    pub fn compile(&mut self, on: bool) {
+        self.compile = on;
+    }
+
+    /// `result` as the host may keep it, see [`Heap::export`].
+    pub fn export(&self, result: EvalResult) -> EvalResult {
+        self.heap.export(result)
+    }
 }
 
 impl Default for Global {
     fn default() -> Self {
-        use builtins::*;
-        let bindings: Vec<(&str, Builtin)> = vec![
-            ("+", add),
-            ("-", sub),
-            ("*", mul),
-            ("/", div),
-            ("\\", lambda),
-            ("=", assign),
-            ("<", less),
-            ("==", eq),
-            ("list", list),
-            ("head", head),
-            ("tail", tail),
-            ("join", join),
-            ("eval", eval),
-            ("def", def),
-            ("if", iff),
-            ("load", load),
-            ("print", print),
-            ("error", error),
-            ("exit", exit),
-        ];
-        let bindings = bindings.into_iter().map(|(s, f)| {
-            (s.to_string(), AST::Function(Function::Builtin(f)))
-        }).collect();
-        Self { bindings }
+        let bindings = builtins().into_iter()
+            .map(|(s, f)| (s.to_string(), AST::Function(f)))
+            .collect();
+        Self {
+            bindings,
+            compile: false,
+            generation: 0,
+            heap: Heap::default(),
+            depth: 0,
+            debugger: None,
+            profiler: None,
+            tests: vec![],
+            docs: Docs::new(),
+        }
     }
 }
 
+/// Every builtin with its name.
+pub fn builtins() -> Vec<(&'static str, Function)> {
+    use builtins::*;
+    type Native = fn(EnvObj, Vec<AST>) -> EvalResult;
+    let bindings: Vec<(&str, Native)> = vec![
+        ("+", add),
+        ("-", sub),
+        ("*", mul),
+        ("/", div),
+        ("floor", floor),
+        ("ceil", ceil),
+        ("round", round),
+        ("truncate", truncate),
+        ("exact->inexact", inexact),
+        ("inexact->exact", exact),
+        ("\\", lambda),
+        ("compile", compile),
+        ("=", assign),
+        ("<", less),
+        ("==", eq),
+        ("list", list),
+        ("head", head),
+        ("tail", tail),
+        ("join", join),
+        ("cons", cons),
+        ("def", def),
+        ("load", load),
+        ("print", print),
+        ("error", error),
+        ("error-kind", error_kind),
+        ("error-message", error_message),
+        ("error-value", error_value),
+        ("exit", exit),
+        ("string-append", strings::append),
+        ("string-length", strings::length),
+        ("substring", strings::substring),
+        ("string-split", strings::split),
+        ("string-index", strings::index),
+        ("string-upcase", strings::upcase),
+        ("string-downcase", strings::downcase),
+        ("string-trim", strings::trim),
+        ("string->number", strings::to_number),
+        ("number->string", strings::from_number),
+        ("string->symbol", strings::to_symbol),
+        ("symbol->string", strings::from_symbol),
+        ("map", lists::map),
+        ("filter", lists::filter),
+        ("reduce", lists::reduce),
+        ("nth", lists::nth),
+        ("last", lists::last),
+        ("reverse", lists::reverse),
+        ("range", lists::range),
+        ("sort", lists::sort),
+        ("zip", lists::zip),
+        ("take", lists::take),
+        ("drop", lists::drop),
+        ("length", lists::length),
+        ("get", maps::get),
+        ("assoc", maps::assoc),
+        ("dissoc", maps::dissoc),
+        ("keys", maps::keys),
+        ("vals", maps::vals),
+        ("ref", refs::new),
+        ("atom", refs::new),
+        ("deref", refs::deref),
+        ("set!", refs::set),
+        ("swap!", refs::swap),
+        ("gc", refs::gc),
+        ("gc-stats", refs::gc_stats),
+        ("assert-eq", assertions::assert_eq),
+        ("deftest", assertions::deftest),
+        ("defmacro", macros::defmacro),
+        ("gensym", macros::gensym_builtin),
+    ];
+    let tail: Vec<(&str, TailBuiltin)> = vec![
+        ("eval", eval),
+        ("if", iff),
+    ];
+    let special: Vec<(&str, TailBuiltin)> = vec![
+        ("and", and),
+        ("or", or),
+        ("cond", cond),
+        ("case", case),
+        ("try", try_catch),
+        ("assert", assertions::assert),
+        ("quasiquote", macros::quasiquote),
+        ("unquote", macros::unquote),
+        ("unquote-splicing", macros::unquote),
+        ("profile", profile::profile),
+    ];
+    bindings.into_iter()
+        .map(|(s, f)| (s, Function::Builtin(s.into(), Rc::new(f))))
+        .chain(tail.into_iter().map(|(s, f)| (s, Function::Tail(s.into(), f))))
+        .chain(special.into_iter()
+               .map(|(s, f)| (s, Function::Special(s.into(), f))))
+        .collect()
+}
+
 impl Env for Global {
-    fn get(&self, key: String) -> EvalResult<AST> {
-        match self.bindings.get(&key) {
+    fn get(&self, key: &str) -> EvalResult<AST> {
+        match self.bindings.get(key) {
             Some(value) => Ok(value.clone()),
-            None => Err(EvalError::UnknownVar(key)),
+            None => Err(EvalError::UnknownVar(key.to_string())),
         }
     }
 
+    fn local(&self, _: &str) -> Option<AST> {
+        None
+    }
+
+    fn global(&mut self) -> EnvObj<'_> {
+        self
+    }
+
     fn define(&mut self, bindings: BindingsVec) {
         self.bindings.extend(bindings);
+        self.generation += 1;
     }
 
     fn assign(&mut self, bindings: BindingsVec) {
         self.define(bindings);
     }
+
+    fn compiling(&self) -> bool {
+        self.compile
+    }
+
+    fn generation(&self) -> Option<usize> {
+        Some(self.generation)
+    }
+
+    fn heap(&mut self) -> Option<&mut Heap> {
+        Some(&mut self.heap)
+    }
+
+    fn debugger(&mut self) -> Option<&mut Option<Debugger>> {
+        Some(&mut self.debugger)
+    }
+
+    fn debugging(&self) -> bool {
+        self.debugger.as_ref().is_some_and(Debugger::is_armed)
+    }
+
+    fn depth(&mut self) -> Option<&mut usize> {
+        Some(&mut self.depth)
+    }
+
+    fn profiler(&mut self) -> Option<&mut Option<Profiler>> {
+        Some(&mut self.profiler)
+    }
+
+    fn tests(&mut self) -> Option<&mut Vec<(String, QExpr)>> {
+        Some(&mut self.tests)
+    }
+
+    fn docs(&mut self) -> Option<&mut Docs> {
+        Some(&mut self.docs)
+    }
+
+    fn roots(&self) -> Vec<AST> {
+        let tests = self.tests.iter().map(|(_, body)| body.clone().into());
+        self.bindings.values().cloned().chain(tests).collect()
+    }
+
+    fn bindings(&self) -> BindingsVec {
+        let mut bindings = self.bindings.clone().into_iter()
+            .collect::<BindingsVec>();
+        bindings.sort_by(|(x, _), (y, _)| x.cmp(y));
+        bindings
+    }
 }
 
 pub struct Scope<'a> {
@@ -92,13 +388,24 @@ impl<'a> Scope<'a> {
 }
 
 impl<'a> Env for Scope<'a> {
-    fn get(&self, key: String) -> EvalResult<AST> {
-        match self.bindings.get(&key) {
+    fn get(&self, key: &str) -> EvalResult<AST> {
+        match self.bindings.get(key) {
             Some(value) => Ok(value.clone()),
             None => self.parent.get(key),
         }
     }
 
+    fn local(&self, key: &str) -> Option<AST> {
+        match self.bindings.get(key) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.local(key),
+        }
+    }
+
+    fn global(&mut self) -> EnvObj<'_> {
+        self.parent.global()
+    }
+
     fn define(&mut self, bindings: BindingsVec) {
         self.parent.define(bindings);
     }
@@ -106,38 +413,111 @@ impl<'a> Env for Scope<'a> {
     fn assign(&mut self, bindings: BindingsVec) {
         self.bindings.extend(bindings);
     }
+
+    fn compiling(&self) -> bool {
+        self.parent.compiling()
+    }
+
+    fn generation(&self) -> Option<usize> {
+        self.parent.generation()
+    }
+
+    fn heap(&mut self) -> Option<&mut Heap> {
+        self.parent.heap()
+    }
+
+    fn debugger(&mut self) -> Option<&mut Option<Debugger>> {
+        self.parent.debugger()
+    }
+
+    fn debugging(&self) -> bool {
+        self.parent.debugging()
+    }
+
+    fn depth(&mut self) -> Option<&mut usize> {
+        self.parent.depth()
+    }
+
+    fn profiler(&mut self) -> Option<&mut Option<Profiler>> {
+        self.parent.profiler()
+    }
+
+    fn tests(&mut self) -> Option<&mut Vec<(String, QExpr)>> {
+        self.parent.tests()
+    }
+
+    fn docs(&mut self) -> Option<&mut Docs> {
+        self.parent.docs()
+    }
+
+    fn roots(&self) -> Vec<AST> {
+        let mut roots = self.parent.roots();
+        roots.extend(self.bindings.values().cloned());
+        roots
+    }
+
+    fn locals(&self) -> BindingsVec {
+        shadow(self.parent.locals(), self.bindings.clone())
+    }
+
+    fn bindings(&self) -> BindingsVec {
+        shadow(self.parent.bindings(), self.bindings.clone())
+    }
 }
 
 mod builtins {
+    use std::rc::Rc;
     use crate::{
-        ast::AST,
+        ast::{AST, Step},
         eval_error::*,
         function::*,
+        number::Number,
         transformers::*,
         qexpr::QExpr,
     };
-    use super::{EnvObj, BindingsVec};
+    use super::{Bindings, BindingsVec, EnvObj, Scope};
 
     pub fn add(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        oftype(|xs: Vec<i128>| Ok(xs.into_iter().sum()))(args)
+        oftype(|xs: Vec<Number>| Ok(xs.into_iter().sum()))(args)
     }
 
     pub fn sub(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        binary_or_unary(|x: i128, y| Ok(match y {
+        binary_or_unary(|x: Number, y| Ok(match y {
             Some(y) => x - y,
             None => -x,
         }))(args)
     }
 
     pub fn mul(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        oftype(|xs: Vec<i128>| Ok(xs.into_iter().product()))(args)
+        oftype(|xs: Vec<Number>| Ok(xs.into_iter().product()))(args)
     }
 
     pub fn div(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        binary(|x: i128, y| match x.checked_div(y) {
-            Some(z) => Ok(z),
-            None => Err(EvalError::Message("div by zero")),
-        })(args)
+        binary(|x: Number, y| x.checked_div(y))(args)
+    }
+
+    pub fn floor(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|x: Number| Ok(x.floor()))(args)
+    }
+
+    pub fn ceil(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|x: Number| Ok(x.ceil()))(args)
+    }
+
+    pub fn round(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|x: Number| Ok(x.round()))(args)
+    }
+
+    pub fn truncate(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|x: Number| Ok(x.truncate()))(args)
+    }
+
+    pub fn inexact(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|x: Number| Ok(x.inexact()))(args)
+    }
+
+    pub fn exact(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(Number::exact)(args)
     }
 
     pub fn list(_: EnvObj, args: Vec<AST>) -> EvalResult {
@@ -156,15 +536,29 @@ mod builtins {
     }
 
     pub fn join(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        oftype(|xs: Vec<QExpr>| Ok(QExpr::from(xs.concat())))(args)
+        oftype(|xs: Vec<QExpr>| {
+            Ok(xs.into_iter().fold(QExpr::from(vec![]), QExpr::join))
+        })(args)
+    }
+
+    pub fn cons(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        binary(|x: AST, list| Ok(list.qexpr()?.cons(x)))(args)
     }
 
-    pub fn eval(env: EnvObj, args: Vec<AST>) -> EvalResult {
-        unary(|x: QExpr| x.eval(env))(args)
+    pub fn eval(_: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        let expr = unary(|x: QExpr| Ok(x))(args)?.qexpr()?;
+        Ok(Step::Eval(expr.sexpr()))
     }
 
     pub fn def(env: EnvObj, args: Vec<AST>) -> EvalResult {
-        env.define(bindings(args)?);
+        let bindings = bindings(args)?.into_iter().map(|(name, value)| {
+            let value = match value {
+                AST::Function(fun) => AST::Function(fun.named(&name)),
+                value => value,
+            };
+            (name, value)
+        });
+        env.define(bindings.collect());
         Ok(AST::default())
     }
 
@@ -187,8 +581,23 @@ mod builtins {
         }
     }
 
-    pub fn lambda(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        binary(|defs: QExpr, body| Lambda::new(defs.symbols()?, body))(args)
+    pub fn lambda(env: EnvObj, args: Vec<AST>) -> EvalResult {
+        binary(|defs: QExpr, body| {
+            let fun = Lambda::new(env, defs.symbols()?, body)?;
+            Ok(match env.compiling() {
+                true => fun.compile(env).ast(),
+                false => fun.ast(),
+            })
+        })(args)
+    }
+
+    /// `compile f` gives `f` running as bytecode, see [`crate::compiler`].
+    /// Other functions are given back as they are.
+    pub fn compile(env: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|fun: AST| Ok(match fun.function()? {
+            Function::Lambda(f) => f.compile(env).ast(),
+            fun => AST::Function(fun),
+        }))(args)
     }
 
     pub fn exit(_: EnvObj, _: Vec<AST>) -> EvalResult {
@@ -196,23 +605,97 @@ mod builtins {
     }
 
     pub fn less(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        binary(|x: i128, y| Ok(bool_int(x < y)))(args)
+        binary(|x: Number, y| Ok(x < y))(args)
     }
 
     pub fn eq(_: EnvObj, args: Vec<AST>) -> EvalResult {
-        binary(|x: AST, y| Ok(AST::Number(bool_int(x == y))))(args)
+        binary(|x: AST, y| Ok(x == y))(args)
     }
 
-    pub fn iff(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    pub fn iff(_: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
         let mut args = args.into_iter();
-        let num = args.next().ok_or(NO_ARGS)?.number()?;
+        let cond = args.next().ok_or(NO_ARGS)?;
         let left = args.next().ok_or(1.expected(3))?.qexpr()?;
         let right = args.next().ok_or(2.expected(3))?.qexpr()?;
-        if num == 0 {
-            right.eval(env)
-        } else {
-            left.eval(env)
+        let branch = if cond.is_truthy() { left } else { right };
+        Ok(Step::Eval(branch.sexpr()))
+    }
+
+    /// `and x ...` evaluates its arguments up to the first false one and gives
+    /// the last value it evaluated.
+    pub fn and(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        until(env, args, false)
+    }
+
+    /// `or x ...` evaluates its arguments up to the first true one and gives
+    /// the last value it evaluated.
+    pub fn or(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        until(env, args, true)
+    }
+
+    fn until(env: EnvObj, args: Vec<AST>, truth: bool) -> EvalResult<Step> {
+        let mut args = args.into_iter();
+        let last = match args.next_back() {
+            Some(last) => last,
+            None => return Ok(Step::Done(AST::Bool(!truth))),
+        };
+        for arg in args {
+            let value = arg.eval(env)?;
+            if value.is_truthy() == truth {
+                return Ok(Step::Done(value));
+            }
+        }
+        Ok(Step::Eval(last))
+    }
+
+    /// `cond {test body ...} ...` runs the body of the first clause whose test
+    /// is true, `else` being always true. A clause without a body gives the
+    /// value of its test.
+    pub fn cond(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        for clause in args {
+            let mut clause = clause.qexpr()?.into_iter();
+            let test = match clause.next().ok_or(EMPTY_QEXPR)? {
+                AST::Symbol(sym, _) if &*sym == "else" => AST::Bool(true),
+                test => test.eval(env)?,
+            };
+            if test.is_truthy() {
+                return body(env, clause.collect(), test);
+            }
+        }
+        Ok(Step::Done(QExpr::from(vec![]).into()))
+    }
+
+    /// `case key {datum body ...} ...` runs the body of the first clause whose
+    /// datum equals the value of `key`. The datum may also be a Q-expr of
+    /// alternatives, or `else` to match anything.
+    pub fn case(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        let mut args = args.into_iter();
+        let key = args.next().ok_or(NO_ARGS)?.eval(env)?;
+        for clause in args {
+            let mut clause = clause.qexpr()?.into_iter();
+            let matches = match clause.next().ok_or(EMPTY_QEXPR)? {
+                AST::Symbol(sym, _) if &*sym == "else" => true,
+                AST::QExpr(data) => data.iter().any(|datum| *datum == key),
+                datum => datum == key,
+            };
+            if matches {
+                return body(env, clause.collect(), key);
+            }
+        }
+        Ok(Step::Done(QExpr::from(vec![]).into()))
+    }
+
+    /// Evaluates `exprs` in order, leaving the last one in tail position.
+    fn body(env: EnvObj, exprs: Vec<AST>, empty: AST) -> EvalResult<Step> {
+        let mut exprs = exprs.into_iter();
+        let last = match exprs.next_back() {
+            Some(last) => last,
+            None => return Ok(Step::Done(empty)),
+        };
+        for expr in exprs {
+            expr.eval(env)?;
         }
+        Ok(Step::Eval(last))
     }
 
     pub fn load(env: EnvObj, args: Vec<AST>) -> EvalResult {
@@ -228,11 +711,88 @@ mod builtins {
 
     pub fn error(_: EnvObj, args: Vec<AST>) -> EvalResult {
         unary(|err: AST| -> EvalResult {
-            Err(EvalError::UserDefined(err.literal()?))
+            Err(EvalError::UserDefined(err))
         })(args)
     }
 
-    fn bool_int(x: bool) -> i128 {
-        if x { 1 } else { 0 }
+    /// `try body clause ...` gives the value of `body`, which is run as code
+    /// if it is a Q-expr. The clauses are `(catch {e} expr ...)`, evaluating
+    /// the `expr`s with `e` bound to the error if `body` fails, and
+    /// `(finally expr ...)`, evaluated whatever happens.
+    pub fn try_catch(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        let mut args = args.into_iter();
+        let body = args.next().ok_or(NO_ARGS)?;
+        let (mut handler, mut cleanup) = (None, None);
+        for arg in args {
+            match clause(&arg) {
+                Some(("catch", rest)) if handler.is_none() =>
+                    handler = Some(Handler::catch(rest)?),
+                Some(("finally", rest)) if cleanup.is_none() =>
+                    cleanup = Some(rest.to_vec()),
+                _ => return Err(EvalError::NotA("try clause", arg)),
+            }
+        }
+        let result = match body {
+            AST::QExpr(body) => body.eval(env),
+            body => body.eval(env),
+        };
+        let result = match handler {
+            Some(handler) => result.or_else(|err| {
+                let err = AST::Error(Rc::new(err.caught()?));
+                handler.handle(env, err)
+            }),
+            None => result,
+        };
+        let cleanup = cleanup.into_iter().flatten()
+            .try_for_each(|expr| expr.eval(env).map(drop));
+        cleanup.and(result).map(Step::Done)
+    }
+
+    /// What a `try` does with an error: the name to bind it to, and the
+    /// expressions to evaluate then.
+    struct Handler(String, Vec<AST>);
+
+    impl Handler {
+        /// The handler of `(catch {e} expr ...)`, given what follows `catch`.
+        fn catch(clause: &[AST]) -> EvalResult<Self> {
+            let (name, body) = clause.split_first().ok_or(NO_ARGS)?;
+            match &name.clone().qexpr()?.symbols()?[..] {
+                [name] => Ok(Handler(name.clone(), body.to_vec())),
+                names => Err(names.len().expected(1)),
+            }
+        }
+
+        fn handle(self, env: EnvObj, err: AST) -> EvalResult {
+            let Handler(name, body) = self;
+            let scope = &mut Scope::new(Bindings::from([(name, err)]), env);
+            body.into_iter()
+                .try_fold(AST::default(), |_, expr| expr.eval(scope))
+        }
+    }
+
+    /// The name and the rest of `(catch x ...)` or `(finally x ...)`.
+    fn clause(ast: &AST) -> Option<(&str, &[AST])> {
+        match ast {
+            AST::SExpr(exprs, _) => match exprs.split_first()? {
+                (AST::Symbol(name, _), rest)
+                    if &**name == "catch" || &**name == "finally" =>
+                    Some((name, rest)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn error_kind(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|err: AST| Ok(AST::from(err.error()?.kind.to_string())))
+            (args)
+    }
+
+    pub fn error_message(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|err: AST| Ok(AST::from(err.error()?.message)))(args)
+    }
+
+    pub fn error_value(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        unary(|err: AST| Ok(err.error()?.value))(args)
     }
 }
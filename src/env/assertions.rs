@@ -0,0 +1,31 @@
+use crate::{ast::{AST, Step}, eval_error::*, transformers::*};
+use super::EnvObj;
+
+/// `assert expr` fails, quoting `expr`, unless it evaluates to a true value.
+pub fn assert(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+    let expr = unary(|expr: AST| Ok(expr))(args)?;
+    match expr.clone().eval(env)?.is_truthy() {
+        true => Ok(Step::Done(AST::default())),
+        false => Err(EvalError::Assertion(expr.to_string())),
+    }
+}
+
+/// `assert-eq expected actual`
+pub fn assert_eq(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|expected: AST, actual| match expected == actual {
+        true => Ok(AST::default()),
+        false => Err(EvalError::Assertion(format!("expected {}, got {}",
+            expected, actual))),
+    })(args)
+}
+
+/// `deftest name {body}` records a test for `lispy test` to run.
+pub fn deftest(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|name: AST, body| {
+        let test = (name.literal()?, body.qexpr()?);
+        if let Some(tests) = env.tests() {
+            tests.push(test);
+        }
+        Ok(AST::default())
+    })(args)
+}
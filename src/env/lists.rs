@@ -0,0 +1,177 @@
+use std::{convert::TryFrom, vec::IntoIter};
+use crate::{
+    ast::AST,
+    eval_error::*,
+    function::Function,
+    qexpr::QExpr,
+    transformers::*,
+};
+use super::EnvObj;
+
+const OUT_OF_RANGE: EvalError = EvalError::Message("index out of range");
+
+/// `map f list`
+pub fn map(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    let (fun, list) = fun_and_list(args)?;
+    let mapped = sequence(list, |x| fun.call(env, one(x)))?;
+    Ok(AST::QExpr(mapped.into()))
+}
+
+/// `filter pred list` keeps the elements for which `pred` is true.
+pub fn filter(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    let (fun, list) = fun_and_list(args)?;
+    let mut kept = vec![];
+    for x in list {
+        if fun.call(env, one(x.clone()))?.is_truthy() {
+            kept.push(x);
+        }
+    }
+    Ok(AST::QExpr(kept.into()))
+}
+
+/// `reduce f init list` folds from the left: `f (f init x1) x2` and so on.
+pub fn reduce(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let fun = args.next().ok_or(NO_ARGS)?.function()?;
+    let init = args.next().ok_or(1.expected(3))?;
+    let list = args.next().ok_or(2.expected(3))?.qexpr()?;
+    if args.len() > 0 {
+        return Err((args.len() + 3).expected(3));
+    }
+    list.into_iter().try_fold(init, |acc, x| {
+        fun.call(env, vec![acc, x].into_iter())
+    })
+}
+
+/// `nth n list`, counting from zero.
+pub fn nth(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let (n, list) = index_and_list(args)?;
+    list.get(n).cloned().ok_or(OUT_OF_RANGE)
+}
+
+pub fn last(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|list: QExpr| list.into_iter().last().ok_or(EMPTY_QEXPR))(args)
+}
+
+pub fn reverse(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|list: QExpr| Ok(list.into_iter().rev().collect::<QExpr>()))(args)
+}
+
+/// `range [start] end [step]`, the integers from `start`, which defaults to
+/// zero, up to but excluding `end`.
+pub fn range(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let args = sequence(args, AST::integer)?;
+    let (start, end, step) = match args[..] {
+        [] => return Err(NO_ARGS),
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => return Err(args.len().expected(3)),
+    };
+    if step == 0 {
+        return Err(EvalError::Message("range with a zero step"));
+    }
+    let ints = std::iter::successors(Some(start), |x| x.checked_add(step))
+        .take_while(|&x| if step > 0 { x < end } else { x > end });
+    Ok(AST::QExpr(ints.map(AST::from).collect()))
+}
+
+/// `sort list [less]` sorts stably, by `less` if given and otherwise by the
+/// natural order of numbers or of strings.
+pub fn sort(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let list = args.next().ok_or(NO_ARGS)?.qexpr()?;
+    let less = args.next().map(AST::function).transpose()?;
+    if args.len() > 0 {
+        return Err((args.len() + 2).expected(2));
+    }
+    let items = list.into_iter().collect();
+    let sorted = match less {
+        Some(less) => merge_sort(items, &mut |x, y| {
+            Ok(less.call(env, vec![x.clone(), y.clone()].into_iter())?
+                .is_truthy())
+        }),
+        None => merge_sort(items, &mut natural_less),
+    }?;
+    Ok(AST::QExpr(sorted.into()))
+}
+
+/// `zip xs ys` pairs up elements, stopping at the end of the shorter list.
+pub fn zip(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|xs: QExpr, ys| Ok(xs.into_iter().zip(ys)
+        .map(|(x, y)| AST::QExpr(vec![x, y].into()))
+        .collect::<QExpr>()))(args)
+}
+
+/// `take n list`, or the whole list if it is shorter.
+pub fn take(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let (n, list) = index_and_list(args)?;
+    Ok(AST::QExpr(list.split_at(n).0))
+}
+
+/// `drop n list`, or nil if the list is shorter.
+pub fn drop(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let (n, list) = index_and_list(args)?;
+    Ok(AST::QExpr(list.split_at(n).1))
+}
+
+pub fn length(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|list: QExpr| Ok(list.len() as i128))(args)
+}
+
+fn one(x: AST) -> IntoIter<AST> {
+    vec![x].into_iter()
+}
+
+fn fun_and_list(args: Vec<AST>) -> EvalResult<(Function, QExpr)> {
+    let mut args = args.into_iter();
+    let fun = args.next().ok_or(NO_ARGS)?.function()?;
+    let list = args.next().ok_or(1.expected(2))?.qexpr()?;
+    match args.len() {
+        0 => Ok((fun, list)),
+        n => Err((n + 2).expected(2)),
+    }
+}
+
+fn index_and_list(args: Vec<AST>) -> EvalResult<(usize, QExpr)> {
+    let mut args = args.into_iter();
+    let n = args.next().ok_or(NO_ARGS)?.integer()?;
+    let list = args.next().ok_or(1.expected(2))?.qexpr()?;
+    if args.len() > 0 {
+        return Err((args.len() + 2).expected(2));
+    }
+    match usize::try_from(n) {
+        Ok(n) => Ok((n, list)),
+        Err(_) => Err(OUT_OF_RANGE),
+    }
+}
+
+fn natural_less(x: &AST, y: &AST) -> EvalResult<bool> {
+    match (x, y) {
+        (AST::Number(x), AST::Number(y)) => Ok(x < y),
+        (AST::Literal(x), AST::Literal(y)) => Ok(x < y),
+        (AST::Number(_), y) => Err(EvalError::NotA("number", y.clone())),
+        (AST::Literal(_), y) => Err(EvalError::NotA("string", y.clone())),
+        (x, _) => Err(EvalError::NotA("number or string", x.clone())),
+    }
+}
+
+fn merge_sort(
+    mut items: Vec<AST>,
+    less: &mut impl FnMut(&AST, &AST) -> EvalResult<bool>,
+) -> EvalResult<Vec<AST>> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, less)?.into_iter();
+    let right = merge_sort(right, less)?.into_iter();
+    let (mut left, mut right) = (left.peekable(), right.peekable());
+    let mut merged = vec![];
+    while let (Some(x), Some(y)) = (left.peek(), right.peek()) {
+        let next = if less(y, x)? { right.next() } else { left.next() };
+        merged.extend(next);
+    }
+    merged.extend(left.chain(right));
+    Ok(merged)
+}
@@ -0,0 +1,51 @@
+use crate::{
+    ast::AST,
+    eval_error::*,
+    map::Map,
+    qexpr::QExpr,
+    transformers::*,
+};
+use super::EnvObj;
+
+/// `get map key [default]`, giving nil or `default` if `key` is absent.
+pub fn get(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let map = args.next().ok_or(NO_ARGS)?.map()?;
+    let key = args.next().ok_or(1.expected(2))?.key()?;
+    let default = args.next().unwrap_or_else(|| QExpr::from(vec![]).into());
+    if args.len() > 0 {
+        return Err((args.len() + 3).expected(3));
+    }
+    Ok(map.get(&key).cloned().unwrap_or(default))
+}
+
+/// `assoc map key value ...`
+pub fn assoc(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let mut map = args.next().ok_or(NO_ARGS)?.map()?;
+    if args.len() == 0 || !args.len().is_multiple_of(2) {
+        let given = args.len() + 1;
+        return Err(given.expected(3.max(given + 1)));
+    }
+    while let (Some(key), Some(value)) = (args.next(), args.next()) {
+        map = map.assoc(key.key()?, value);
+    }
+    Ok(AST::Map(map))
+}
+
+/// `dissoc map key ...`
+pub fn dissoc(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let map = args.next().ok_or(NO_ARGS)?.map()?;
+    let keys = sequence(args, AST::key)?;
+    Ok(AST::Map(keys.iter().fold(map, Map::dissoc)))
+}
+
+pub fn keys(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|map: Map| Ok(map.keys().cloned().map(AST::from).collect::<QExpr>()))
+        (args)
+}
+
+pub fn vals(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|map: Map| Ok(map.values().cloned().collect::<QExpr>()))(args)
+}
@@ -0,0 +1,26 @@
+use std::fs;
+use crate::{ast::{AST, Step}, eval_error::*, profiler, transformers::*};
+use super::EnvObj;
+
+/// `profile expr` evaluates `expr` and prints how often the functions it
+/// called ran and for how long, see [`crate::profiler`]. `profile expr file`
+/// writes their stacks to `file` in the folded format instead. Within
+/// another profile, `expr` is only evaluated.
+pub fn profile(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+    binary_or_unary(|expr: AST, file: Option<AST>| {
+        let file = match file {
+            Some(file) => Some(file.eval(env)?.literal()?),
+            None => None,
+        };
+        if !profiler::start(env) {
+            return expr.eval(env);
+        }
+        let result = expr.eval(env);
+        let profile = profiler::finish(env).expect("a profile was started");
+        match file {
+            Some(file) => fs::write(file, profile.folded())?,
+            None => print!("{}", profile.report()),
+        }
+        result
+    })(args).map(Step::Done)
+}
@@ -0,0 +1,53 @@
+use crate::{ast::AST, eval_error::*, reference::Ref, transformers::*};
+use super::EnvObj;
+
+pub fn new(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|value: AST| {
+        let cell = Ref::new(value);
+        if let Some(heap) = env.heap() {
+            heap.track(&cell);
+        }
+        Ok(AST::Ref(cell))
+    })(args)
+}
+
+pub fn deref(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|cell: AST| Ok(cell.reference()?.get()))(args)
+}
+
+/// `set! ref value`, giving `value`.
+pub fn set(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|cell: AST, value: AST| {
+        cell.reference()?.set(value.clone());
+        Ok(value)
+    })(args)
+}
+
+/// `swap! ref f args...` stores `f current args...` and gives it. The cell
+/// is not locked while `f` runs, so `f` may itself read it.
+pub fn swap(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let cell = args.next().ok_or(NO_ARGS)?.reference()?;
+    let fun = args.next().ok_or(1.expected(2))?.function()?;
+    let args = std::iter::once(cell.get()).chain(args).collect::<Vec<_>>();
+    let value = fun.call(env, args.into_iter())?;
+    cell.set(value.clone());
+    Ok(value)
+}
+
+/// `gc ()` runs a collection, with the variables of the calls being run
+/// among the roots, see [`crate::heap`]. Like `exit`, it ignores its
+/// arguments.
+pub fn gc(env: EnvObj, _: Vec<AST>) -> EvalResult {
+    if let Some(heap) = env.heap() {
+        heap.request();
+    }
+    super::collect_garbage(env, None);
+    Ok(AST::default())
+}
+
+/// `gc-stats ()` gives the number of `live` cells, of cells `allocated`
+/// since the last collection, of `collections` and of cells they `freed`.
+pub fn gc_stats(env: EnvObj, _: Vec<AST>) -> EvalResult {
+    Ok(AST::Map(env.heap().map(|heap| heap.stats()).unwrap_or_default()))
+}
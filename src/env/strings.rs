@@ -0,0 +1,80 @@
+use crate::{
+    ast::AST,
+    eval_error::*,
+    number::Number,
+    parser,
+    qexpr::QExpr,
+    span::Span,
+    transformers::*,
+};
+use super::EnvObj;
+
+pub fn append(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    oftype(|xs: Vec<String>| Ok(xs.concat()))(args)
+}
+
+pub fn length(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| Ok(s.chars().count() as i128))(args)
+}
+
+pub fn substring(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    let mut args = args.into_iter();
+    let string = args.next().ok_or(NO_ARGS)?.literal()?;
+    let start = args.next().ok_or(1.expected(2))?.integer()?;
+    let end = args.next().map(AST::integer).transpose()?;
+    if args.len() > 0 {
+        return Err((args.len() + 3).expected(3));
+    }
+    let len = string.chars().count() as i128;
+    let end = end.unwrap_or(len);
+    if start < 0 || end < start || end > len {
+        return Err(EvalError::Message("substring out of range"));
+    }
+    let chars = string.chars().skip(start as usize);
+    Ok(AST::from(chars.take((end - start) as usize).collect::<String>()))
+}
+
+pub fn split(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|s: String, sep: String| Ok(match sep.as_str() {
+        "" => s.chars().map(|c| AST::from(c.to_string())).collect(),
+        sep => s.split(sep).map(|x| AST::from(x.to_string()))
+            .collect::<QExpr>(),
+    }))(args)
+}
+
+pub fn index(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|s: String, sub: String| Ok(match s.find(&sub) {
+        Some(i) => s[..i].chars().count() as i128,
+        None => -1,
+    }))(args)
+}
+
+pub fn upcase(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| Ok(s.to_uppercase()))(args)
+}
+
+pub fn downcase(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| Ok(s.to_lowercase()))(args)
+}
+
+pub fn trim(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| Ok(s.trim().to_string()))(args)
+}
+
+pub fn to_number(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| {
+        parser::number(&s).ok_or(EvalError::Message("not a number"))
+    })(args)
+}
+
+pub fn from_number(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|x: Number| Ok(x.to_string()))(args)
+}
+
+pub fn to_symbol(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|s: String| Ok(AST::Symbol(s.into(), Span::default())))(args)
+}
+
+pub fn from_symbol(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|sym: AST| sym.symbol())(args)
+}
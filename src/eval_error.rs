@@ -1,6 +1,8 @@
-use super::ast::AST;
+use super::{ast::AST, parser::ParseError, span::Span};
 use std::convert::Infallible;
+use std::fmt;
 use std::io;
+use std::rc::Rc;
 
 pub type EvalResult<T = AST> = Result<T, EvalError>;
 
@@ -10,39 +12,176 @@ pub enum EvalError {
     UnknownVar(String),
     ArgsMismatch(Unexpected),
     Message(&'static str),
-    UserDefined(String),
+    UserDefined(AST),
+    Assertion(String),
     ReadError(io::Error),
+    /// A file that does not parse, see [`crate::Interpreter::load`].
+    Parse(ParseError),
     Exit,
+    At(Span, Box<EvalError>),
+    Trace(Vec<Frame>, Box<EvalError>),
 }
 
+/// A lambda call that was active when an error escaped it.
+#[derive(Debug)]
+pub struct Frame {
+    pub name: Option<String>,
+    /// The arguments as passed, those after `&` included one by one.
+    pub args: Vec<AST>,
+    pub span: Span,
+}
+
+/// An error caught by `try`, as seen from Lisp code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorValue {
+    pub kind: &'static str,
+    pub message: String,
+    pub value: AST,
+}
+
+/// How many of the innermost frames a backtrace shows.
+const TRACE_LIMIT: usize = 16;
+
 pub const NO_ARGS: EvalError = EvalError::Message("no arguments");
 pub const EMPTY_QEXPR: EvalError = EvalError::Message("qexpr is empty");
+pub const TOO_DEEP: EvalError = EvalError::Message("calls nest too deeply");
 pub const DEF_ERROR: EvalError =
     EvalError::Message("symbol and value lists have different lengths");
 
+impl EvalError {
+    /// Records that the error happened while evaluating the expression at
+    /// `span`, unless a more precise location is already known.
+    pub fn at(self, span: &Span) -> Self {
+        match self {
+            err @ (EvalError::At(..) | EvalError::Trace(..)) => err,
+            err @ EvalError::Exit => err,
+            err if !span.is_known() => err,
+            err => EvalError::At(span.clone(), Box::new(err)),
+        }
+    }
+
+    /// A short name for the kind of failure, as seen by `try` handlers.
+    pub fn kind(&self) -> &'static str {
+        use EvalError::*;
+        match self {
+            NotA(..) => "type",
+            UnknownVar(_) => "unbound",
+            ArgsMismatch(_) => "arity",
+            Message(_) => "runtime",
+            UserDefined(AST::Error(err)) => err.kind,
+            UserDefined(_) => "user",
+            Assertion(_) => "assertion",
+            ReadError(_) => "io",
+            Parse(_) => "parse",
+            Exit => "exit",
+            At(_, err) | Trace(_, err) => err.kind(),
+        }
+    }
+
+    /// Turns the error into a value for a handler, unless it is `Exit`,
+    /// which cannot be caught.
+    pub fn caught(self) -> Result<ErrorValue, EvalError> {
+        let kind = self.kind();
+        match self {
+            EvalError::Exit => Err(self),
+            EvalError::At(_, err) | EvalError::Trace(_, err) => err.caught(),
+            EvalError::UserDefined(AST::Error(err)) =>
+                Ok(Rc::unwrap_or_clone(err)),
+            EvalError::UserDefined(value) =>
+                Ok(ErrorValue { kind, message: message(&value), value }),
+            err => {
+                let message = err.to_string();
+                let value = AST::from(message.clone());
+                Ok(ErrorValue { kind, message, value })
+            },
+        }
+    }
+
+    /// Records that the error escaped the call described by `frame`.
+    pub fn within(self, frame: Frame) -> Self {
+        match self {
+            EvalError::Trace(mut frames, err) => {
+                frames.push(frame);
+                EvalError::Trace(frames, err)
+            },
+            err @ EvalError::Exit => err,
+            err => EvalError::Trace(vec![frame], Box::new(err)),
+        }
+    }
+}
+
 impl From<Infallible> for EvalError {
     fn from(_: Infallible) -> Self {
         panic!("Like that will ever happen.")
     }
 }
 
+impl From<ParseError> for EvalError {
+    fn from(err: ParseError) -> Self {
+        EvalError::Parse(err)
+    }
+}
+
 impl From<io::Error> for EvalError {
     fn from(err: io::Error) -> Self {
         EvalError::ReadError(err)
     }
 }
 
-impl ToString for EvalError {
-    fn to_string(&self) -> String {
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use EvalError::*;
         match self {
-            NotA(typ, ast) => format!("expected {}, got {}", typ, ast.typ()),
-            UnknownVar(s) => format!("unknown variable: {}", s),
-            ArgsMismatch(u) => format!("args mismatch: {}", u.to_string()),
-            Message(s) => s.to_string(),
-            UserDefined(s) => format!("exception: {}", s),
-            ReadError(err) => format!("error reading file: {}", err),
-            Exit => "exiting.".to_string(),
+            NotA(typ, ast) => write!(f, "expected {}, got {}", typ, ast.typ()),
+            UnknownVar(s) => write!(f, "unknown variable: {}", s),
+            ArgsMismatch(u) => write!(f, "args mismatch: {}", u),
+            Message(s) => f.write_str(s),
+            UserDefined(AST::Error(err)) if err.kind != "user" =>
+                f.write_str(&err.message),
+            UserDefined(value) => write!(f, "exception: {}", message(value)),
+            Assertion(s) => write!(f, "assertion failed: {}", s),
+            ReadError(err) => write!(f, "error reading file: {}", err),
+            Parse(err) => write!(f, "parse error: {}", err),
+            Exit => f.write_str("exiting."),
+            At(span, err) => write!(f, "{}: {}", span, err),
+            Trace(frames, err) => {
+                writeln!(f, "traceback (most recent call last):")?;
+                if frames.len() > TRACE_LIMIT {
+                    writeln!(f, "  ... {} more", frames.len() - TRACE_LIMIT)?;
+                }
+                for frame in frames.iter().take(TRACE_LIMIT).rev() {
+                    writeln!(f, "{}", frame)?;
+                }
+                err.fmt(f)
+            },
+        }
+    }
+}
+
+fn message(value: &AST) -> String {
+    match value {
+        AST::Literal(message) => message.to_string(),
+        AST::Error(err) => err.message.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl fmt::Display for ErrorValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} error: {}>", self.kind, self.message)
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.name.as_deref().unwrap_or("<lambda>");
+        let call = std::iter::once(name.to_string())
+            .chain(self.args.iter().map(AST::to_string))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match self.span.is_known() {
+            true => write!(f, "  ({}) defined at {}", call, self.span),
+            false => write!(f, "  ({})", call),
         }
     }
 }
@@ -53,9 +192,9 @@ pub struct Unexpected {
     pub given: usize,
 }
 
-impl ToString for Unexpected {
-    fn to_string(&self) -> String {
-        format!("{} expected, {} given", self.expected, self.given)
+impl fmt::Display for Unexpected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} expected, {} given", self.expected, self.given)
     }
 }
 
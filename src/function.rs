@@ -1,20 +1,73 @@
 use std::vec::IntoIter;
-use std::fmt;
-use super::{env::*, ast::AST, eval_error::*, qexpr::QExpr};
+use std::{fmt, rc::Rc};
+use super::{
+    env::*,
+    ast::{AST, Step},
+    compiler::{self, Chunk},
+    debugger,
+    eval_error::*,
+    profiler::{self, Callee},
+    qexpr::QExpr,
+    span::Span,
+    vm,
+};
 
-pub type Builtin = fn(EnvObj, Vec<AST>) -> EvalResult;
+/// How deeply lambda calls may nest, tail calls aside, before evaluation
+/// fails instead of taking ever more memory.
+pub const MAX_DEPTH: usize = 10_000;
 
+/// Calls are run on a new stack segment of `STACK_SEGMENT` bytes when less
+/// than `RED_ZONE` is left of the current one, so that nesting is limited
+/// by [`MAX_DEPTH`] rather than the stack of the thread.
+const RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+/// A function written in Rust. It may be a closure holding state of its
+/// own, see [`crate::Interpreter::register`].
+pub type Builtin = Rc<dyn Fn(EnvObj, Vec<AST>) -> EvalResult>;
+pub type TailBuiltin = fn(EnvObj, Vec<AST>) -> EvalResult<Step>;
+
+/// A builtin comes with the name it was registered under, for profiles.
 #[derive(Clone)]
 pub enum Function {
-    Builtin(Builtin),
-    Lambda(Lambda),
+    Builtin(Rc<str>, Builtin),
+    Tail(Rc<str>, TailBuiltin),
+    /// A special form: its arguments are passed in unevaluated.
+    Special(Rc<str>, TailBuiltin),
+    Lambda(Rc<Lambda>),
+    /// A lambda from code to code, see `defmacro`.
+    Macro(Rc<Lambda>),
 }
 
 impl Function {
+    /// Remembers the name a lambda was first bound to, for backtraces.
+    pub fn named(self, name: &str) -> Self {
+        match self {
+            Function::Lambda(f) => Function::Lambda(f.named(name)),
+            Function::Macro(f) => Function::Macro(f.named(name)),
+            fun => fun,
+        }
+    }
+
     pub fn call(&self, env: EnvObj, args: IntoIter<AST>) -> EvalResult {
         match self {
-            Function::Builtin(f) => f(env, args.collect()),
-            Function::Lambda(f) => f.call(env, args),
+            Function::Lambda(f) => f.clone().step(args)?.run(env),
+            Function::Macro(f) => f.clone().expand(env, args.collect())?
+                .eval(env),
+            builtin => builtin.clone().step(env, args)?.run(env),
+        }
+    }
+
+    pub fn step(self, env: EnvObj, args: IntoIter<AST>) -> EvalResult<Step> {
+        match self {
+            Function::Builtin(name, f) =>
+                profiler::frame(env, Callee::Builtin(&name),
+                    |env| f(env, args.collect())).map(Step::Done),
+            Function::Tail(name, f) | Function::Special(name, f) =>
+                profiler::frame(env, Callee::Builtin(&name),
+                    |env| f(env, args.collect())),
+            Function::Lambda(f) => f.step(args),
+            Function::Macro(f) => f.expand(env, args.collect()).map(Step::Eval),
         }
     }
 }
@@ -22,8 +75,10 @@ impl Function {
 impl fmt::Debug for Function {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Function::Builtin(_) => fmt.write_str("<function>"),
-            Function::Lambda(f) => f.fmt(fmt),
+            Function::Builtin(..) | Function::Tail(..) =>
+                fmt.write_str("<function>"),
+            Function::Special(..) => fmt.write_str("<special form>"),
+            Function::Lambda(f) | Function::Macro(f) => f.fmt(fmt),
         }
     }
 }
@@ -31,33 +86,58 @@ impl fmt::Debug for Function {
 impl PartialEq for Function {
     fn eq(&self, other: &Function) -> bool {
         match (self, other) {
-            (Function::Builtin(x), Function::Builtin(y)) =>
-                x as *const Builtin == y as *const Builtin,
+            (Function::Builtin(_, x), Function::Builtin(_, y)) =>
+                Rc::ptr_eq(x, y),
+            (Function::Tail(_, x), Function::Tail(_, y)) =>
+                *x as usize == *y as usize,
+            (Function::Special(_, x), Function::Special(_, y)) =>
+                *x as usize == *y as usize,
             (Function::Lambda(x), Function::Lambda(y)) => x == y,
+            (Function::Macro(x), Function::Macro(y)) => x == y,
             _ => false,
         }
     }
 }
 
-impl ToString for Function {
-    fn to_string(&self) -> String {
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Function::Builtin(_) => "<function>".to_string(),
-            Function::Lambda(f) => f.to_string(),
+            Function::Builtin(..) | Function::Tail(..) =>
+                f.write_str("<function>"),
+            Function::Special(..) => f.write_str("<special form>"),
+            Function::Lambda(lambda) => lambda.fmt(f),
+            Function::Macro(lambda) => write!(f, "<macro {}>", lambda),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Lambda {
+    name: Option<String>,
     context: Context,
     params: Vec<String>,
     vararg: Option<String>,
     body: QExpr,
+    /// The body as an S-expression, built once rather than on every call.
+    code: AST,
+    compiled: Compiled,
+}
+
+/// The bytecode of a lambda, if it was compiled. It does not change what
+/// the lambda does, so it is left out of comparisons.
+#[derive(Clone, Debug, Default)]
+struct Compiled(Option<Rc<Chunk>>);
+
+impl PartialEq for Compiled {
+    fn eq(&self, _: &Compiled) -> bool {
+        true
+    }
 }
 
 impl Lambda {
-    pub fn new(mut params: Vec<String>, body: QExpr) -> EvalResult {
+    pub fn new(env: &dyn Env, mut params: Vec<String>, body: QExpr)
+        -> EvalResult<Self>
+    {
         let tail_pos = params.iter()
             .position(|x| x == "&")
             .unwrap_or(params.len());
@@ -67,43 +147,199 @@ impl Lambda {
             Err(EvalError::Message("more than one param after &"))
         } else {
             std::mem::drop(tail);
+            let mut context = Context::default();
+            context.capture(env, body.iter(), &params, &vararg);
+            let code = body.clone().sexpr();
             Ok(Self {
-                context: Context::default(),
-                params, vararg, body,
-            } .ast())
+                name: None,
+                context,
+                params,
+                vararg,
+                body,
+                code,
+                compiled: Compiled::default(),
+            })
         }
     }
 
-    fn ast(self) -> AST {
-        AST::Function(Function::Lambda(self))
+    /// The same lambda running as bytecode, see
+    /// [`crate::Interpreter::compile`].
+    pub fn compile(&self, env: &dyn Env) -> Self {
+        let mut captured = self.context.0.keys().cloned().collect::<Vec<_>>();
+        captured.sort();
+        let params = self.params.iter().chain(&self.vararg);
+        let arity = params.clone().count();
+        let locals = params.cloned().chain(captured).collect();
+        let chunk = compiler::compile(env, locals, arity, &self.code);
+        Self { compiled: Compiled(Some(Rc::new(chunk))), ..self.clone() }
+    }
+
+    pub fn ast(self) -> AST {
+        AST::Function(Function::Lambda(Rc::new(self)))
+    }
+
+    /// The parameters as written, with `&` before the vararg if any.
+    pub fn params(&self) -> Vec<&str> {
+        let vararg = self.vararg.iter().flat_map(|vararg| ["&", vararg]);
+        self.params.iter().map(String::as_str).chain(vararg).collect()
+    }
+
+    /// The name the lambda was first bound to, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The body as an S-expression. Partial applications and compiled
+    /// copies share it, so its address tells a lambda apart from the
+    /// others, but not from those.
+    pub fn code(&self) -> &AST {
+        &self.code
+    }
+
+    /// Where the body was written.
+    pub fn span(&self) -> &Span {
+        self.body.span()
+    }
+
+    /// The values the lambda holds: its captured variables and its body, in
+    /// both forms.
+    pub fn values(&self) -> impl Iterator<Item = &AST> {
+        self.context.0.values().chain(self.body.iter()).chain(Some(&self.code))
+    }
+
+    fn named(self: Rc<Self>, name: &str) -> Rc<Self> {
+        match self.name {
+            Some(_) => self,
+            None => Rc::new(Self {
+                name: Some(name.to_string()),
+                ..Rc::unwrap_or_clone(self)
+            }),
+        }
     }
 
-    fn call(&self, env: EnvObj, args: IntoIter<AST>) -> EvalResult {
+    fn step(self: Rc<Self>, args: IntoIter<AST>) -> EvalResult<Step> {
         let (expected, given) = (self.params.len(), args.len());
         use std::cmp::Ordering::*;
         match (given.cmp(&expected), &self.vararg) {
-            (Less, _) => Ok(self.curry(args).ast()),
+            (Less, _) => Ok(Step::Done(self.curry(args).ast())),
             (Greater, None) => Err(EvalError::ArgsMismatch(Unexpected {
                 expected, given
             })),
-            _ => self.apply(env, args),
+            _ => Ok(Step::Apply(self, args.collect())),
+        }
+    }
+
+    /// Runs a macro on its unevaluated arguments, giving the code to evaluate
+    /// in place of the call. Unlike functions, macros are never curried.
+    pub fn expand(self: Rc<Self>, env: EnvObj, args: Vec<AST>) -> EvalResult {
+        let (expected, given) = (self.params.len(), args.len());
+        match self.step(args.into_iter())? {
+            Step::Apply(fun, args) => fun.apply(env, args),
+            _ => Err(given.expected(expected)),
         }
     }
 
     fn curry(&self, args: IntoIter<AST>) -> Self {
         let n = args.len();
         Self {
+            name: self.name.clone(),
             context: self.extend(args),
             params: Vec::from(&self.params[n..]),
             vararg: self.vararg.clone(),
             body: self.body.clone(),
+            code: self.code.clone(),
+            compiled: self.compiled.clone(),
+        }
+    }
+
+    /// Runs the body in a fresh scope. Calls in tail position are run here
+    /// in turn instead of recursing, so they take constant stack space.
+    pub fn apply(self: Rc<Self>, env: EnvObj, args: Vec<AST>) -> EvalResult {
+        nested(env, |env| {
+            let (mut current, mut args) = (self, args);
+            loop {
+                match current.run(env, args)? {
+                    Step::Apply(fun, next) => {
+                        current = fun;
+                        args = next;
+                    },
+                    step => return step.run(env),
+                }
+            }
+        })
+    }
+
+    /// Evaluates the body up to a value or a lambda to apply next, in a
+    /// frame of the profiler if a profile is being taken.
+    fn run(&self, env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        profiler::frame(env, Callee::Lambda(self),
+                        |env| self.run_body(env, args))
+    }
+
+    fn run_body(&self, env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+        match &self.compiled.0 {
+            Some(chunk) if !env.debugging() => {
+                let slots = &mut self.slots(chunk, args);
+                vm::run(chunk, slots, env).map_err(|err| err.within(
+                    self.frame(|param| chunk.local(param, slots))))
+            },
+            _ => {
+                let (context, body) = self.enter(args);
+                let scope = &mut context.scope(env.global());
+                let debugged = scope.debugging();
+                if debugged {
+                    let frame = self.frame(|param| scope.local(param));
+                    debugger::on_enter(scope, frame);
+                }
+                let step = body.eval_tail(scope).map_err(|err| err.within(
+                    self.frame(|param| scope.local(param))));
+                if debugged {
+                    debugger::on_leave(scope);
+                }
+                step
+            },
+        }
+    }
+
+    /// Describes a call of this lambda whose arguments are given by `local`.
+    fn frame(&self, local: impl Fn(&str) -> Option<AST>) -> Frame {
+        let mut args = self.params.iter()
+            .filter_map(|param| local(param))
+            .collect::<Vec<_>>();
+        match self.vararg.as_deref().and_then(&local) {
+            Some(AST::QExpr(rest)) => args.extend(rest.iter().cloned()),
+            Some(rest) => args.push(rest),
+            None => {},
         }
+        Frame { name: self.name.clone(), args, span: self.body.span().clone() }
+    }
+
+    /// The values of the slots of `chunk` for a call with `args`.
+    fn slots(&self, chunk: &Chunk, mut args: Vec<AST>) -> Vec<AST> {
+        if self.vararg.is_some() {
+            let tail = args.drain(self.params.len()..).collect();
+            args.push(AST::QExpr(tail));
+        }
+        let bound = match args.len() == chunk.params {
+            true => &chunk.locals[args.len()..],
+            false => {
+                let params = self.params.iter().chain(&self.vararg);
+                let mut bindings = self.context.0.clone();
+                bindings.extend(params.cloned().zip(args));
+                return chunk.locals.iter()
+                    .map(|name| bindings.remove(name).unwrap_or_default())
+                    .collect();
+            },
+        };
+        let context = bound.iter()
+            .map(|name| self.context.0.get(name).cloned().unwrap_or_default());
+        args.extend(context);
+        args
     }
 
-    fn apply(&self, env: EnvObj, args: IntoIter<AST>) -> EvalResult {
+    fn enter(&self, mut args: Vec<AST>) -> (Context, AST) {
         let context = match &self.vararg {
             Some(vararg) => {
-                let mut args: Vec<_> = args.collect();
                 let tail = args.drain(self.params.len()..).collect();
                 let mut context = self.extend(args);
                 context.0.insert(vararg.clone(), AST::QExpr(tail));
@@ -111,7 +347,7 @@ impl Lambda {
             },
             None => self.extend(args)
         };
-        self.body.clone().eval(&mut context.scope(env))
+        (context, self.code.clone())
     }
 
     fn extend(&self, args: impl IntoIterator<Item = AST>) -> Context {
@@ -119,9 +355,23 @@ impl Lambda {
     }
 }
 
-impl ToString for Lambda {
-    fn to_string(&self) -> String {
-        format!("(\\ {{{}}} {})", self.params.join(" "), self.body.to_string())
+/// Runs `f` one call deeper, see [`MAX_DEPTH`].
+fn nested(env: EnvObj, f: impl FnOnce(EnvObj) -> EvalResult) -> EvalResult {
+    match env.depth() {
+        Some(depth) if *depth >= MAX_DEPTH => return Err(TOO_DEEP),
+        Some(depth) => *depth += 1,
+        None => {},
+    }
+    let result = stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, || f(env));
+    if let Some(depth) = env.depth() {
+        *depth -= 1;
+    }
+    result
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(\\ {{{}}} {})", self.params().join(" "), self.body)
     }
 }
 
@@ -129,8 +379,30 @@ impl ToString for Lambda {
 struct Context(Bindings);
 
 impl Context {
+    fn capture<'a>(&mut self,
+                   env: &dyn Env,
+                   body: impl IntoIterator<Item = &'a AST>,
+                   params: &[String],
+                   vararg: &Option<String>) {
+        for ast in body {
+            match ast {
+                AST::Symbol(sym, _) if self.0.contains_key(&**sym)
+                    || params.iter().any(|param| **param == **sym)
+                    || vararg.as_deref() == Some(sym) => {},
+                AST::Symbol(sym, _) => if let Some(value) = env.local(sym) {
+                    self.0.insert(sym.to_string(), value);
+                },
+                AST::SExpr(asts, _) =>
+                    self.capture(env, asts.iter(), params, vararg),
+                AST::QExpr(asts) =>
+                    self.capture(env, asts.iter(), params, vararg),
+                _ => {},
+            }
+        }
+    }
+
     fn extend(&self,
-              params: &Vec<String>,
+              params: &[String],
               args: impl IntoIterator<Item = AST>) -> Self {
         let mut bindings = self.0.clone();
         bindings.extend(params.iter().cloned().zip(args));
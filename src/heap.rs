@@ -0,0 +1,268 @@
+//! The cells made by `ref`, tracked so that cycles through them are freed.
+//!
+//! Every other value is immutable, so it can only hold values made before
+//! it and reference counting frees it. A cell can end up holding itself,
+//! as in `(set! r (list r))` or a closure stored in a cell it captures, and
+//! such a cycle keeps its count above zero forever. The heap keeps a weak
+//! pointer to every cell. A collection marks the cells reachable from the
+//! roots and empties the others, which breaks their cycles and lets
+//! reference counting do the rest.
+//!
+//! A collection runs when one is due after an evaluation, see
+//! [`crate::Interpreter::eval`], or after a top-level form of a file being
+//! loaded, and whenever `gc ()` is called. The roots are the global
+//! bindings, the tests, the value just computed and the variables of the
+//! calls being run, see [`crate::env::Env::roots`].
+//!
+//! Whatever else holds a cell, the host or a Rust frame for instance, shows
+//! in its reference count. A collection first counts the references the
+//! cells, and the values they lead to, make to each other: a cell, lambda,
+//! S-expression or error with more references than that is held from
+//! outside, and is a root too. The storage lists and maps share has no
+//! count to read, so the interpreter gives the host copies of them, see
+//! [`Heap::export`]. A list that only a Rust frame holds while `gc ()` runs,
+//! such as an argument evaluated before the call, is not seen though: if it
+//! shares its storage with a cell nothing leads to, its cells are emptied.
+
+use std::{collections::{HashMap, HashSet}, rc::Rc};
+use super::{
+    ast::AST,
+    eval_error::{EvalError, EvalResult, Frame},
+    function::Function,
+    map::{Key, Map},
+    qexpr::QExpr,
+    reference::{Ref, WeakRef},
+};
+
+/// How many cells are made before the first collection is due.
+const FIRST_COLLECTION: usize = 1024;
+
+pub struct Heap {
+    cells: Vec<WeakRef>,
+    /// The cells made since the last collection.
+    allocated: usize,
+    /// How many cells to make before the next collection is due.
+    threshold: usize,
+    requested: bool,
+    collections: usize,
+    freed: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            cells: vec![],
+            allocated: 0,
+            threshold: FIRST_COLLECTION,
+            requested: false,
+            collections: 0,
+            freed: 0,
+        }
+    }
+}
+
+impl Heap {
+    pub fn track(&mut self, cell: &Ref) {
+        self.cells.push(cell.downgrade());
+        self.allocated += 1;
+    }
+
+    /// Makes a collection due at the end of the current evaluation.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.requested || self.allocated >= self.threshold
+    }
+
+    /// Empties the cells that neither `roots` nor anything held from
+    /// outside the heap leads to, giving how many.
+    pub fn collect<'a>(&mut self, roots: impl IntoIterator<Item = &'a AST>)
+        -> usize
+    {
+        let cells = self.cells.iter()
+            .filter_map(WeakRef::upgrade)
+            .collect::<Vec<_>>();
+        let outside = Census::take(&cells).outside(&cells);
+        let mut marker = Marker::default();
+        for root in roots.into_iter().cloned().chain(outside) {
+            marker.mark(root);
+        }
+        let (kept, freed) = cells.into_iter()
+            .partition::<Vec<_>, _>(|cell| marker.cells.contains(&cell.id()));
+        for cell in &freed {
+            cell.set(AST::default());
+        }
+        self.cells = kept.iter().map(Ref::downgrade).collect();
+        self.collections += 1;
+        self.freed += freed.len();
+        self.allocated = 0;
+        self.threshold = FIRST_COLLECTION.max(2 * self.cells.len());
+        self.requested = false;
+        freed.len()
+    }
+
+    /// `result`, to be handed to the host, with its lists and maps copied
+    /// so that they share no storage with those the heap walks. Without a
+    /// cell there is nothing to lose, and nothing is copied.
+    pub fn export(&self, result: EvalResult) -> EvalResult {
+        match self.cells.is_empty() {
+            true => result,
+            false => result.map(unshare).map_err(unshare_error),
+        }
+    }
+
+    /// The counters shown by `gc-stats`.
+    pub fn stats(&self) -> Map {
+        let live = self.cells.iter().filter(|cell| cell.is_alive()).count();
+        let stats = vec![
+            ("live", live),
+            ("allocated", self.allocated),
+            ("collections", self.collections),
+            ("freed", self.freed),
+        ];
+        stats.into_iter()
+            .map(|(name, n)| (Key::Symbol(name.into()), AST::from(n as i128)))
+            .collect()
+    }
+}
+
+fn unshare(value: AST) -> AST {
+    match value {
+        AST::QExpr(xs) => {
+            let copy = xs.iter().cloned().map(unshare).collect();
+            AST::QExpr(QExpr::new(copy, xs.span().clone()))
+        },
+        AST::Map(map) => AST::Map(map.keys().cloned()
+            .zip(map.values().cloned().map(unshare))
+            .collect()),
+        value => value,
+    }
+}
+
+fn unshare_error(err: EvalError) -> EvalError {
+    match err {
+        EvalError::NotA(what, value) => EvalError::NotA(what, unshare(value)),
+        EvalError::UserDefined(value) => EvalError::UserDefined(unshare(value)),
+        EvalError::At(span, err) =>
+            EvalError::At(span, Box::new(unshare_error(*err))),
+        EvalError::Trace(frames, err) => {
+            let frames = frames.into_iter()
+                .map(|frame| Frame {
+                    args: frame.args.into_iter().map(unshare).collect(),
+                    ..frame
+                })
+                .collect();
+            EvalError::Trace(frames, Box::new(unshare_error(*err)))
+        },
+        err => err,
+    }
+}
+
+/// How many references the cells, and the values they lead to, hold to
+/// each cell, lambda, non-empty S-expression and error among those values.
+/// Each place a reference is stored is counted once, however many lists
+/// share it, so the counts never exceed the reference counts.
+#[derive(Default)]
+struct Census {
+    /// The addresses of the values counted so far.
+    places: HashSet<usize>,
+    cells: HashMap<usize, usize>,
+    /// The other values counted, each with a copy to read its count from.
+    values: HashMap<usize, (AST, usize)>,
+}
+
+impl Census {
+    fn take(cells: &[Ref]) -> Self {
+        let mut census = Self::default();
+        for cell in cells {
+            let content = cell.peek();
+            let mut stack = vec![&*content];
+            while let Some(ast) = stack.pop() {
+                if !census.places.insert(ast as *const AST as usize) {
+                    continue;
+                }
+                match ast {
+                    AST::Ref(cell) =>
+                        *census.cells.entry(cell.id()).or_default() += 1,
+                    AST::QExpr(exprs) => stack.extend(exprs.iter()),
+                    AST::Map(map) => stack.extend(map.values()),
+                    AST::SExpr(exprs, _) if !exprs.is_empty()
+                        && census.count(ast, exprs.as_ptr() as usize) =>
+                        stack.extend(exprs.iter()),
+                    AST::Function(Function::Lambda(fun) | Function::Macro(fun))
+                        if census.count(ast, Rc::as_ptr(fun) as usize) =>
+                        stack.extend(fun.values()),
+                    AST::Error(err)
+                        if census.count(ast, Rc::as_ptr(err) as usize) =>
+                        stack.push(&err.value),
+                    _ => {},
+                }
+            }
+        }
+        census
+    }
+
+    /// Counts a reference to `ast`, telling whether it is the first.
+    fn count(&mut self, ast: &AST, id: usize) -> bool {
+        let (_, count) = self.values.entry(id).or_insert((ast.clone(), 0));
+        *count += 1;
+        *count == 1
+    }
+
+    /// The cells and values with references the census did not count.
+    /// One reference to each is `cells` or the census itself.
+    fn outside(self, cells: &[Ref]) -> Vec<AST> {
+        let counted = |id| self.cells.get(&id).copied().unwrap_or(0);
+        let cells = cells.iter()
+            .filter(|cell| cell.strong_count() > 1 + counted(cell.id()))
+            .map(|cell| AST::Ref(cell.clone()));
+        let values = self.values.values()
+            .filter(|(value, count)| strong_count(value) > 1 + count)
+            .map(|(value, _)| value.clone());
+        cells.chain(values).collect()
+    }
+}
+
+fn strong_count(value: &AST) -> usize {
+    match value {
+        AST::SExpr(exprs, _) => Rc::strong_count(exprs),
+        AST::Function(Function::Lambda(fun) | Function::Macro(fun)) =>
+            Rc::strong_count(fun),
+        AST::Error(err) => Rc::strong_count(err),
+        _ => 1,
+    }
+}
+
+/// The cells and lambdas seen so far, each being walked only once.
+#[derive(Default)]
+struct Marker {
+    cells: HashSet<usize>,
+    lambdas: HashSet<usize>,
+}
+
+impl Marker {
+    /// Walks `root` and the contents of the cells it leads to, with a stack
+    /// rather than recursion so that long chains of cells fit.
+    fn mark(&mut self, root: AST) {
+        let mut contents = vec![root];
+        while let Some(content) = contents.pop() {
+            let mut stack = vec![&content];
+            while let Some(ast) = stack.pop() {
+                match ast {
+                    AST::SExpr(exprs, _) => stack.extend(exprs.iter()),
+                    AST::QExpr(exprs) => stack.extend(exprs.iter()),
+                    AST::Map(map) => stack.extend(map.values()),
+                    AST::Ref(cell) if self.cells.insert(cell.id()) =>
+                        contents.push(cell.get()),
+                    AST::Function(Function::Lambda(fun) | Function::Macro(fun))
+                        if self.lambdas.insert(Rc::as_ptr(fun) as usize) =>
+                        stack.extend(fun.values()),
+                    AST::Error(err) => stack.push(&err.value),
+                    _ => {},
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::{fmt, path::Path};
+use super::{
+    ast::AST,
+    debugger::Debugger,
+    env::{self, Env, Global},
+    eval_error::{EvalError, EvalResult},
+    function::{Builtin, Function},
+    macros,
+    parser::{ParseError, parse},
+    profiler::{self, Profiler},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    Eval(EvalError),
+}
+
+impl From<EvalError> for Error {
+    fn from(err: EvalError) -> Self {
+        Error::Eval(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "parse error: {}", err),
+            Error::Eval(err) => write!(f, "eval error: {}", err),
+        }
+    }
+}
+
+/// A Lisp session: the global environment plus ways to feed it source code.
+pub struct Interpreter {
+    global: Global,
+    prelude: bool,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// A session with the prelude already loaded.
+    pub fn new() -> Self {
+        Self { global: Global::with_prelude(), prelude: true }
+    }
+
+    /// A session with nothing but the builtins.
+    pub fn without_prelude() -> Self {
+        Self { global: Global::default(), prelude: false }
+    }
+
+    /// Makes the lambdas defined from now on run as bytecode. On the loops
+    /// of `benches/count.rs` they run five to six times as fast as walked.
+    pub fn compile(&mut self, on: bool) {
+        self.global.compile(on);
+    }
+
+    /// Tells whether the lambdas defined from now on run as bytecode.
+    pub fn compiling(&self) -> bool {
+        self.global.compiling()
+    }
+
+    /// Forgets every definition, as if the session had just started. Whether
+    /// lambdas are compiled and the debugger attached stay as they were.
+    pub fn reset(&mut self) {
+        let compile = self.global.compiling();
+        let debugger = self.global.debugger().and_then(Option::take);
+        self.global = match self.prelude {
+            true => Global::with_prelude(),
+            false => Global::default(),
+        };
+        self.global.compile(compile);
+        *self.global.debugger().unwrap() = debugger;
+    }
+
+    /// Parses `source`, expands its macros and evaluates it globally.
+    ///
+    /// The garbage collector may run afterwards. It keeps the values the
+    /// host holds, whichever call gave them.
+    pub fn eval(&mut self, source: &str) -> Result<AST, Error> {
+        let tree = parse(source).map_err(Error::Parse)?;
+        let result = macros::expand(tree, &mut self.global)
+            .and_then(|tree| tree.eval(&mut self.global));
+        Ok(self.collect(result)?)
+    }
+
+    /// Evaluates the expressions of the file at `path` in turn, stopping at
+    /// the first that fails.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> EvalResult {
+        let result = env::load(&mut self.global, path);
+        self.collect(result)
+    }
+
+    /// Looks up a global binding.
+    pub fn get(&self, name: &str) -> Option<AST> {
+        self.global.export(self.global.get(name)).ok()
+    }
+
+    pub fn define(&mut self, name: &str, value: AST) {
+        self.global.define(vec![(name.to_string(), value)]);
+    }
+
+    /// Makes a host function callable from Lisp under `name`.
+    pub fn register(&mut self, name: &str, fun: Builtin) {
+        self.define(name, AST::Function(Function::Builtin(name.into(), fun)));
+    }
+
+    /// The names of the tests defined with `deftest`, in order.
+    pub fn tests(&mut self) -> Vec<String> {
+        let tests = self.global.tests().unwrap().iter();
+        tests.map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Runs the body of the test called `name` in this session. To run it
+    /// on its own, as `lispy test` does, load its file in a fresh session.
+    pub fn run_test(&mut self, name: &str) -> EvalResult {
+        let body = self.global.tests().unwrap().iter()
+            .find(|(test, _)| test == name)
+            .map(|(_, body)| body.clone());
+        let result = match body {
+            Some(body) => body.eval(&mut self.global),
+            None => Err(EvalError::UnknownVar(name.to_string())),
+        };
+        self.collect(result)
+    }
+
+    /// Collects garbage if due, keeping what `result` holds, and gives
+    /// `result` for the host to keep. Errors may hold values too, so
+    /// collecting waits for a success.
+    fn collect(&mut self, result: EvalResult) -> EvalResult {
+        if let Ok(value) = &result {
+            env::collect_garbage(&mut self.global, Some(value));
+        }
+        self.global.export(result)
+    }
+
+    /// Makes evaluation stop where `debugger` says, see [`Debugger`].
+    pub fn attach(&mut self, debugger: Debugger) {
+        *self.global.debugger().unwrap() = Some(debugger);
+    }
+
+    /// The debugger attached with [`Interpreter::attach`], if any.
+    pub fn debugger(&mut self) -> Option<&mut Debugger> {
+        self.global.debugger().and_then(Option::as_mut)
+    }
+
+    /// Starts taking a profile of what is evaluated, see [`Profiler`].
+    pub fn start_profile(&mut self) {
+        profiler::start(&mut self.global);
+    }
+
+    /// Stops the profile started with [`Interpreter::start_profile`],
+    /// giving it.
+    pub fn finish_profile(&mut self) -> Option<Profiler> {
+        profiler::finish(&mut self.global)
+    }
+
+    /// The global environment, whose values are not exported, see
+    /// [`Heap::export`](crate::heap::Heap::export).
+    pub(crate) fn global(&mut self) -> &mut Global {
+        &mut self.global
+    }
+}
@@ -0,0 +1,43 @@
+//! An embeddable interpreter for a small Lisp dialect.
+//!
+//! The [`Interpreter`] is the entry point for host programs. The types
+//! re-exported here are those its methods take and give: the values, the
+//! errors, the builtins a host may register and the debugger and profiler
+//! it may attach. The REPL of the `lispy` binary is built on [`Command`]
+//! and [`is_complete`].
+
+pub(crate) mod ast;
+pub(crate) mod compiler;
+pub(crate) mod debugger;
+pub(crate) mod env;
+pub(crate) mod eval_error;
+pub(crate) mod function;
+pub(crate) mod heap;
+pub(crate) mod macros;
+pub(crate) mod map;
+pub(crate) mod number;
+pub(crate) mod parser;
+pub(crate) mod profiler;
+pub(crate) mod qexpr;
+pub(crate) mod reference;
+pub(crate) mod repl;
+pub(crate) mod span;
+pub(crate) mod transformers;
+pub(crate) mod vm;
+
+mod interpreter;
+
+pub use ast::AST;
+pub use debugger::{Console, Debugger};
+pub use env::{Env, EnvObj};
+pub use eval_error::{ErrorValue, EvalError, EvalResult, Frame};
+pub use function::{Builtin, Function, Lambda};
+pub use interpreter::{Error, Interpreter};
+pub use map::{Key, Map};
+pub use number::Number;
+pub use parser::{ParseError, is_complete};
+pub use profiler::{CountingAlloc, Profiler, Stats};
+pub use qexpr::QExpr;
+pub use reference::Ref;
+pub use repl::Command;
+pub use span::Span;
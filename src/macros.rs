@@ -0,0 +1,142 @@
+//! Macros: lambdas that take their arguments unevaluated and return the code
+//! to evaluate in place of the call.
+//!
+//! Templates are written with quasiquote, `` `(if ,c {,body} {nil}) ``, where
+//! `,x` is replaced by the value of `x` and `,@xs` splices the elements of the
+//! list `xs`. Symbols ending in `#` inside a template are renamed to fresh
+//! names on every expansion, so the bindings a macro introduces can never
+//! capture or shadow the variables of its caller.
+
+use std::{collections::HashMap, rc::Rc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::{
+    ast::{AST, Step},
+    env::EnvObj,
+    eval_error::*,
+    function::{Function, Lambda},
+    qexpr::QExpr,
+    span::Span,
+    transformers::*,
+};
+
+/// Expands every call of a macro bound in `env` found in `ast`, before it is
+/// evaluated. Q-expressions are searched as well since they hold the bodies
+/// of lambdas, but a Q-expression is never a call itself: a body such as
+/// `{when c x}` is expanded when it is evaluated instead.
+pub fn expand(ast: AST, env: EnvObj) -> EvalResult {
+    match ast {
+        AST::SExpr(exprs, span) => match macro_called(&exprs, env) {
+            Some(fun) => {
+                let args = exprs[1..].to_vec();
+                let code = fun.expand(env, args).map_err(|e| e.at(&span))?;
+                expand(code, env)
+            },
+            None if is_form(&exprs, "quasiquote") =>
+                Ok(AST::SExpr(exprs, span)),
+            None => {
+                let exprs = exprs.iter().cloned();
+                let exprs = sequence(exprs, |x| expand(x, env))?;
+                Ok(AST::SExpr(exprs.into(), span))
+            },
+        },
+        AST::QExpr(exprs) =>
+            Ok(AST::QExpr(exprs.try_map(|x| expand(x, env))?)),
+        ast => Ok(ast),
+    }
+}
+
+fn macro_called(exprs: &[AST], env: EnvObj) -> Option<Rc<Lambda>> {
+    match exprs.first() {
+        Some(AST::Symbol(head, _)) => match env.get(head) {
+            Ok(AST::Function(Function::Macro(fun))) => Some(fun),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Tells whether `exprs` is `(name x)`.
+fn is_form(exprs: &[AST], name: &str) -> bool {
+    match exprs {
+        [AST::Symbol(head, _), _] => &**head == name,
+        _ => false,
+    }
+}
+
+/// A symbol starting with `prefix` that no program text can spell.
+pub fn gensym(prefix: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!("{}#{}", prefix, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+pub fn defmacro(env: EnvObj, args: Vec<AST>) -> EvalResult {
+    binary(|defs: QExpr, body| {
+        let mut syms = defs.symbols()?.into_iter();
+        let name = syms.next().ok_or(EMPTY_QEXPR)?;
+        let fun = Lambda::new(env, syms.collect(), body)?;
+        let fun = Function::Macro(Rc::new(fun));
+        env.define(vec![(name.clone(), AST::Function(fun.named(&name)))]);
+        Ok(AST::default())
+    })(args)
+}
+
+pub fn quasiquote(env: EnvObj, args: Vec<AST>) -> EvalResult<Step> {
+    let template = unary(|x: AST| Ok(x))(args)?;
+    Template { env, names: HashMap::new() }.fill(template).map(Step::Done)
+}
+
+pub fn unquote(_: EnvObj, _: Vec<AST>) -> EvalResult<Step> {
+    Err(EvalError::Message("unquote outside of quasiquote"))
+}
+
+pub fn gensym_builtin(_: EnvObj, args: Vec<AST>) -> EvalResult {
+    unary(|prefix: String|
+          Ok(AST::Symbol(gensym(&prefix).into(), Span::default())))(args)
+}
+
+struct Template<'a> {
+    env: EnvObj<'a>,
+    names: HashMap<String, String>,
+}
+
+impl<'a> Template<'a> {
+    fn fill(&mut self, template: AST) -> EvalResult {
+        match template {
+            AST::SExpr(exprs, span) if is_form(&exprs, "unquote") =>
+                exprs[1].clone().eval(self.env).map_err(|e| e.at(&span)),
+            AST::SExpr(exprs, span) =>
+                Ok(AST::SExpr(self.fill_all(exprs.to_vec())?.into(), span)),
+            AST::QExpr(exprs) => {
+                let span = exprs.span().clone();
+                let exprs = self.fill_all(exprs.into_iter().collect())?;
+                Ok(AST::QExpr(QExpr::new(exprs, span)))
+            },
+            AST::Symbol(sym, span) if sym.len() > 1 && sym.ends_with('#') => {
+                let fresh = self.names.entry(sym.to_string())
+                    .or_insert_with_key(|sym| gensym(&sym[..sym.len() - 1]));
+                Ok(AST::Symbol(fresh.as_str().into(), span))
+            },
+            ast => Ok(ast),
+        }
+    }
+
+    fn fill_all(&mut self, exprs: Vec<AST>) -> EvalResult<Vec<AST>> {
+        let mut filled = vec![];
+        for expr in exprs {
+            match expr {
+                AST::SExpr(xs, span) if is_form(&xs, "unquote-splicing") => {
+                    let spliced = xs[1].clone().eval(self.env)
+                        .and_then(|list| match list {
+                            AST::QExpr(list) => Ok(list.into_iter().collect()),
+                            AST::SExpr(list, _) => Ok(list.to_vec()),
+                            ast => Err(EvalError::NotA("Q-expr", ast)),
+                        })
+                        .map_err(|e| e.at(&span))?;
+                    filled.extend(spliced);
+                },
+                expr => filled.push(self.fill(expr)?),
+            }
+        }
+        Ok(filled)
+    }
+}
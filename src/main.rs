@@ -1,56 +1,186 @@
-mod ast;
-mod env;
-mod eval_error;
-mod function;
-mod parser;
-mod qexpr;
-mod transformers;
-
-use std::fs::File;
+use std::fs::{self, File};
 use rustyline::{self, Editor};
-use env::{Global, load};
-use parser::parse;
-use eval_error::EvalError;
+use lispy::{
+    Command, Console, CountingAlloc, Debugger, Error, EvalError, Interpreter,
+    is_complete,
+};
 
 const HIST_FILE: &str = ".lisp_history";
 
+const USAGE: &str = "usage: lispy [--no-prelude] [--compile] \
+                     [--profile[=file]] file ...
+       lispy [--no-prelude] [--compile] [test file ...]";
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
 fn main() {
-    let mut args = std::env::args();
-    args.next().unwrap();
-    if args.len() > 0 {
-        run_interpreter(args);
-    } else if let Err(err) = run_repl() {
+    let (flags, files): (Vec<_>, Vec<_>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    if let Some(flag) = flags.iter().find(|flag| !is_known(flag)) {
+        eprintln!("unknown flag {}\n{}", flag, USAGE);
+        std::process::exit(2);
+    }
+    let has = |name: &str| flags.iter().any(|flag| flag == name);
+    let (prelude, compile) = (!has("--no-prelude"), has("--compile"));
+    let profile = flags.iter().find_map(|flag| match flag.as_str() {
+        "--profile" => Some(None),
+        flag => flag.strip_prefix("--profile=").map(Some),
+    });
+    let new = move || {
+        let mut interpreter = match prelude {
+            true => Interpreter::new(),
+            false => Interpreter::without_prelude(),
+        };
+        interpreter.compile(compile);
+        interpreter
+    };
+    let testing = files.first().map(String::as_str) == Some("test");
+    if profile.is_some() && (testing || files.is_empty()) {
+        eprintln!("--profile only applies to running files\n{}", USAGE);
+        std::process::exit(2);
+    }
+    let interpreter = new();
+    if testing {
+        if !run_tests(new, &files[1..]) {
+            std::process::exit(1);
+        }
+    } else if !files.is_empty() {
+        run_interpreter(interpreter, files, profile);
+    } else if let Err(err) = run_repl(interpreter) {
         println!("{}", err);
     }
 }
 
-fn run_interpreter(args: std::env::Args) {
-    let env = &mut Global::default();
-    for filename in args {
-        if let Err(err) = load(env, filename) {
-            println!("load error: {}", err.to_string());
+fn is_known(flag: &str) -> bool {
+    matches!(flag, "--no-prelude" | "--compile" | "--profile")
+        || flag.starts_with("--profile=")
+}
+
+/// Loads every file. With `--profile`, a report on the functions called
+/// is printed afterwards, or with `--profile=file` their stacks are written
+/// to `file`.
+fn run_interpreter(mut interpreter: Interpreter,
+                   files: Vec<String>,
+                   profile: Option<Option<&str>>) {
+    if profile.is_some() {
+        interpreter.start_profile();
+    }
+    for filename in files {
+        if let Err(err) = interpreter.load(filename) {
+            println!("load error: {}", err);
+        }
+    }
+    let profiler = interpreter.finish_profile();
+    match (profile, profiler) {
+        (Some(Some(file)), Some(profiler)) =>
+            if let Err(err) = fs::write(file, profiler.folded()) {
+                println!("profile error: {}", err);
+            },
+        (Some(None), Some(profiler)) => print!("{}", profiler.report()),
+        _ => {},
+    }
+}
+
+/// Runs every test of every file, each in a fresh interpreter that has
+/// just loaded the file, so that no test sees what another did. Tells
+/// whether they all passed.
+fn run_tests(new: impl Fn() -> Interpreter, files: &[String]) -> bool {
+    let (mut passed, mut failures) = (0, vec![]);
+    let load = |file: &String| {
+        let mut interpreter = new();
+        interpreter.load(file).map(|_| interpreter)
+    };
+    for file in files {
+        let mut loaded = match load(file) {
+            Ok(interpreter) => Some(interpreter),
+            Err(err) => {
+                failures.push((file.clone(), err));
+                continue;
+            },
+        };
+        let names = loaded.as_mut().unwrap().tests();
+        for name in names {
+            let test = format!("{}: {}", file, name);
+            let interpreter = match loaded.take() {
+                Some(interpreter) => Ok(interpreter),
+                None => load(file),
+            };
+            let result = interpreter
+                .and_then(|mut interpreter| interpreter.run_test(&name));
+            match result {
+                Ok(_) => {
+                    println!("test {} ... ok", test);
+                    passed += 1;
+                },
+                Err(err) => {
+                    println!("test {} ... FAILED", test);
+                    failures.push((test, err));
+                },
+            }
         }
     }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (test, err) in &failures {
+            println!("\n---- {} ----\n{}", test, err);
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed",
+             status, passed, failures.len());
+    failures.is_empty()
+}
+
+/// Reads the debugger commands from the terminal.
+struct Terminal(Editor<()>);
+
+impl Console for Terminal {
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let line = self.0.readline(prompt).ok()?;
+        self.0.add_history_entry(&line);
+        Some(line)
+    }
+
+    fn print(&mut self, text: &str) {
+        println!("{}", text);
+    }
 }
 
-fn run_repl() -> rustyline::Result<()> {
+fn run_repl(mut interpreter: Interpreter) -> rustyline::Result<()> {
+    interpreter.attach(Debugger::new(Box::new(Terminal(Editor::new()))));
     let mut editor = Editor::<()>::new();
-    if let Err(_) = editor.load_history(HIST_FILE) {
+    if editor.load_history(HIST_FILE).is_err() {
         File::create(HIST_FILE)?;
     }
-    let env = &mut Global::default();
-    while let Ok(line) = editor.readline("lispy> ") {
-        editor.add_history_entry(&line);
-        match parse(&line) {
-            Ok(tree) => match tree.eval(env) {
-                Ok(tree) => println!("{}", tree.to_string()),
-                Err(e @ EvalError::Exit) => {
-                    println!("{}", e.to_string());
-                    break;
-                },
-                Err(err) => println!("eval error: {}", err.to_string()),
+    while let Ok(mut source) = editor.readline("lispy> ") {
+        while !is_complete(&source) {
+            match editor.readline("...> ") {
+                Ok(line) => source = source + "\n" + &line,
+                Err(_) => break,
+            }
+        }
+        editor.add_history_entry(&source);
+        let result = match Command::parse(&source) {
+            Some(Ok(Command::Quit)) => break,
+            Some(Ok(command)) => command.run(&mut interpreter),
+            Some(Err(usage)) => {
+                println!("{}", usage);
+                continue;
+            },
+            None if interpreter.debugger().unwrap().command(&source) =>
+                continue,
+            None => interpreter.eval(&source).map(|tree| tree.to_string()),
+        };
+        interpreter.debugger().unwrap().finish();
+        match result {
+            Ok(output) => println!("{}", output),
+            Err(Error::Eval(e @ EvalError::Exit)) => {
+                println!("{}", e);
+                break;
             },
-            Err(e) => println!("parse error: {}", e),
+            Err(err) => println!("{}", err),
         }
     }
     editor.append_history(HIST_FILE)
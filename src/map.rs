@@ -0,0 +1,98 @@
+use std::{cmp::Ordering, fmt, iter::FromIterator, rc::Rc};
+use im_rc::OrdMap;
+use super::{ast::AST, number::Number, span::Span};
+
+/// An immutable dictionary, written `#{key value ...}`. Its entries are kept
+/// sorted by key so that equal maps print the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map(OrdMap<Key, AST>);
+
+/// The values that can be used as map keys. Numbers are compared numerically,
+/// like `==` does, so `1` and `1.0` are the same key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Key {
+    Number(Number),
+    Symbol(Rc<str>),
+    Literal(Rc<str>),
+}
+
+impl Map {
+    pub fn get(&self, key: &Key) -> Option<&AST> {
+        self.0.get(key)
+    }
+
+    pub fn assoc(mut self, key: Key, value: AST) -> Self {
+        self.0.insert(key, value);
+        self
+    }
+
+    pub fn dissoc(mut self, key: &Key) -> Self {
+        self.0.remove(key);
+        self
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.0.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &AST> {
+        self.0.values()
+    }
+}
+
+impl FromIterator<(Key, AST)> for Map {
+    fn from_iter<I>(iter: I) -> Self where I: IntoIterator<Item = (Key, AST)> {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = self.0.iter()
+            .map(|(key, value)|
+                 format!("{} {}", AST::from(key.clone()), value))
+            .collect::<Vec<_>>();
+        write!(f, "#{{{}}}", entries.join(" "))
+    }
+}
+
+impl Key {
+    fn rank(&self) -> u8 {
+        match self {
+            Key::Number(_) => 0,
+            Key::Symbol(_) => 1,
+            Key::Literal(_) => 2,
+        }
+    }
+}
+
+impl From<Key> for AST {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Number(x) => AST::Number(x),
+            Key::Symbol(x) => AST::Symbol(x, Span::default()),
+            Key::Literal(x) => AST::Literal(x),
+        }
+    }
+}
+
+/// NaN is never made a key, so numbers are totally ordered here.
+impl Eq for Key {}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        match (self, other) {
+            (Key::Number(x), Key::Number(y)) =>
+                x.partial_cmp(y).unwrap_or(Ordering::Equal),
+            (Key::Symbol(x), Key::Symbol(y)) => x.cmp(y),
+            (Key::Literal(x), Key::Literal(y)) => x.cmp(y),
+            (x, y) => x.rank().cmp(&y.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::{Product, Sum},
+    ops::{Add, Mul, Neg, Sub},
+};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+use super::eval_error::{EvalError, EvalResult};
+
+/// The numeric tower: exact integers and rationals, inexact floats.
+/// Operations promote their arguments to the least exact kind involved.
+///
+/// Integers are kept in `Int` whenever they fit into an `i128`, so `Big` is
+/// only ever seen once a computation has left that range.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Int(i128),
+    Big(BigInt),
+    Ratio(BigRational),
+    Float(f64),
+}
+
+pub const DIV_BY_ZERO: EvalError = EvalError::Message("div by zero");
+
+enum Pair {
+    Ints(i128, i128),
+    Bigs(BigInt, BigInt),
+    Ratios(BigRational, BigRational),
+    Floats(f64, f64),
+}
+
+impl Number {
+    fn pair(self, other: Number) -> Pair {
+        use Number::*;
+        match (self, other) {
+            (Int(x), Int(y)) => Pair::Ints(x, y),
+            (Float(x), y) => Pair::Floats(x, y.to_f64()),
+            (x, Float(y)) => Pair::Floats(x.to_f64(), y),
+            (x @ Ratio(_), y) | (x, y @ Ratio(_)) =>
+                Pair::Ratios(x.into_ratio(), y.into_ratio()),
+            (x, y) => Pair::Bigs(x.into_big(), y.into_big()),
+        }
+    }
+
+    fn into_big(self) -> BigInt {
+        match self {
+            Number::Int(x) => BigInt::from(x),
+            Number::Big(x) => x,
+            _ => unreachable!("only integers are widened to bigints"),
+        }
+    }
+
+    fn into_ratio(self) -> BigRational {
+        match self {
+            Number::Ratio(x) => x,
+            Number::Float(_) =>
+                unreachable!("floats are only made exact on request"),
+            x => BigRational::from_integer(x.into_big()),
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(x) => *x as f64,
+            Number::Big(x) => x.to_f64().unwrap_or(f64::NAN),
+            Number::Ratio(x) => x.to_f64().unwrap_or(f64::NAN),
+            Number::Float(x) => *x,
+        }
+    }
+
+    pub fn integer(x: BigInt) -> Number {
+        match x.to_i128() {
+            Some(x) => Number::Int(x),
+            None => Number::Big(x),
+        }
+    }
+
+    pub fn ratio(x: BigRational) -> Number {
+        if x.is_integer() {
+            Number::integer(x.to_integer())
+        } else {
+            Number::Ratio(x)
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(x) => *x == 0,
+            Number::Big(x) => x.is_zero(),
+            Number::Ratio(x) => x.is_zero(),
+            Number::Float(x) => *x == 0.0,
+        }
+    }
+
+    pub fn checked_div(self, other: Number) -> EvalResult<Number> {
+        match self.pair(other) {
+            Pair::Floats(x, y) => Ok(Number::Float(x / y)),
+            Pair::Ints(_, 0) => Err(DIV_BY_ZERO),
+            Pair::Ints(x, y) =>
+                Ok(Number::ratio(BigRational::new(x.into(), y.into()))),
+            Pair::Bigs(x, y) if !y.is_zero() =>
+                Ok(Number::ratio(BigRational::new(x, y))),
+            Pair::Ratios(x, y) if !y.is_zero() => Ok(Number::ratio(x / y)),
+            _ => Err(DIV_BY_ZERO),
+        }
+    }
+
+    pub fn floor(self) -> Number {
+        self.round_with(BigRational::floor, f64::floor)
+    }
+
+    pub fn ceil(self) -> Number {
+        self.round_with(BigRational::ceil, f64::ceil)
+    }
+
+    pub fn round(self) -> Number {
+        self.round_with(BigRational::round, f64::round)
+    }
+
+    pub fn truncate(self) -> Number {
+        self.round_with(BigRational::trunc, f64::trunc)
+    }
+
+    fn round_with(self,
+                  exact: impl FnOnce(&BigRational) -> BigRational,
+                  inexact: impl FnOnce(f64) -> f64) -> Number {
+        match self {
+            Number::Ratio(x) => Number::ratio(exact(&x)),
+            Number::Float(x) => Number::Float(inexact(x)),
+            integer => integer,
+        }
+    }
+
+    pub fn inexact(self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    pub fn exact(self) -> EvalResult<Number> {
+        match self {
+            Number::Float(x) => BigRational::from_float(x)
+                .map(Number::ratio)
+                .ok_or(EvalError::Message("float is not finite")),
+            exact => Ok(exact),
+        }
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, other: Number) -> Number {
+        match self.pair(other) {
+            Pair::Ints(x, y) => x.checked_add(y).map(Number::Int)
+                .unwrap_or_else(|| Number::integer(BigInt::from(x) + y)),
+            Pair::Bigs(x, y) => Number::integer(x + y),
+            Pair::Ratios(x, y) => Number::ratio(x + y),
+            Pair::Floats(x, y) => Number::Float(x + y),
+        }
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, other: Number) -> Number {
+        match self.pair(other) {
+            Pair::Ints(x, y) => x.checked_sub(y).map(Number::Int)
+                .unwrap_or_else(|| Number::integer(BigInt::from(x) - y)),
+            Pair::Bigs(x, y) => Number::integer(x - y),
+            Pair::Ratios(x, y) => Number::ratio(x - y),
+            Pair::Floats(x, y) => Number::Float(x - y),
+        }
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, other: Number) -> Number {
+        match self.pair(other) {
+            Pair::Ints(x, y) => x.checked_mul(y).map(Number::Int)
+                .unwrap_or_else(|| Number::integer(BigInt::from(x) * y)),
+            Pair::Bigs(x, y) => Number::integer(x * y),
+            Pair::Ratios(x, y) => Number::ratio(x * y),
+            Pair::Floats(x, y) => Number::Float(x * y),
+        }
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        Number::Int(0) - self
+    }
+}
+
+impl Sum for Number {
+    fn sum<I: Iterator<Item = Number>>(iter: I) -> Number {
+        iter.fold(Number::Int(0), Add::add)
+    }
+}
+
+impl Product for Number {
+    fn product<I: Iterator<Item = Number>>(iter: I) -> Number {
+        iter.fold(Number::Int(1), Mul::mul)
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match self.clone().pair(other.clone()) {
+            Pair::Ints(x, y) => x.partial_cmp(&y),
+            Pair::Bigs(x, y) => x.partial_cmp(&y),
+            Pair::Ratios(x, y) => x.partial_cmp(&y),
+            Pair::Floats(x, y) => x.partial_cmp(&y),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Int(x) => x.fmt(f),
+            Number::Big(x) => x.fmt(f),
+            Number::Ratio(x) => x.fmt(f),
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
@@ -1,59 +1,228 @@
-use std::str::FromStr;
-use super::{ast::AST, qexpr::QExpr};
+use super::{
+    ast::AST, eval_error::*, map::Map, number::Number, qexpr::QExpr, span::Span,
+};
+use std::collections::BTreeMap;
+use num_bigint::BigInt;
+use num_rational::BigRational;
 use nom::{
     IResult,
     branch::alt,
     bytes::complete::tag,
-    combinator::{all_consuming, into, map, map_res, opt, recognize, value},
+    combinator::{all_consuming, map, map_res, not, opt, recognize, value},
     character::complete::*,
     error::Error,
     multi::{many0, many1},
-    sequence::{delimited, preceded},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
 };
+use nom_locate::LocatedSpan;
+
+/// Source text that knows where it starts.
+pub type Input<'a> = LocatedSpan<&'a str, Span>;
+pub type MyResult<'a, T = AST, E = Error<Input<'a>>> = IResult<Input<'a>, T, E>;
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub span: Span,
+    pub near: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: cannot parse `{}`", self.span, self.near)
+    }
+}
+
+pub fn parse(string: &str) -> Result<AST, ParseError> {
+    parse_at(string, Span::start(None))
+}
+
+/// Parses `string` as if it was found at `start`, so that the spans of the
+/// resulting expressions point into the original source.
+pub fn parse_at(string: &str, start: Span) -> Result<AST, ParseError> {
+    let input = Input::new_extra(string, start);
+    let parser =
+        map(located(parse_many), |(xs, span)| AST::SExpr(xs.into(), span));
+    match all_consuming(parser)(input.clone()) {
+        Ok((_, result)) => Ok(result),
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) =>
+            Err(ParseError {
+                span: span_of(&err.input),
+                near: err.input.fragment().lines().next()
+                    .unwrap_or_default().chars().take(20).collect(),
+            }),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError {
+            span: span_of(&input),
+            near: string.to_string(),
+        }),
+    }
+}
 
-pub type MyResult<'a, T = AST, E = Error<&'a str>> = IResult<&'a str, T, E>;
+/// Reads a number literal surrounded by nothing but whitespace.
+pub fn number(string: &str) -> Option<Number> {
+    let input = Input::new_extra(string.trim(), Span::default());
+    all_consuming(parse_number)(input).ok().map(|(_, x)| x)
+}
+
+/// Splits source text into top-level forms, each with the number of the line
+/// it starts on. A form is a line together with the lines following it up to
+/// the one where its brackets are balanced.
+pub fn forms(string: &str) -> Vec<(u32, &str)> {
+    let mut forms = vec![];
+    let (mut start, mut line) = (0, 1);
+    let ends = string.match_indices('\n').map(|(i, _)| i);
+    for (number, end) in (1..).zip(ends.chain(Some(string.len()))) {
+        if is_complete(&string[start..end]) {
+            forms.push((line, &string[start..end]));
+            start = (end + 1).min(string.len());
+            line = number + 1;
+        }
+    }
+    if start < string.len() {
+        forms.push((line, &string[start..]));
+    }
+    forms
+}
+
+/// The comment lines just above each top-level form, without their `;`, by
+/// the line the form starts on. A blank line ends a comment.
+pub fn doc_comments(string: &str) -> BTreeMap<u32, Vec<String>> {
+    let (mut docs, mut comments) = (BTreeMap::new(), vec![]);
+    for (line, form) in forms(string) {
+        match form.trim() {
+            "" => comments.clear(),
+            form => match form.strip_prefix(';') {
+                Some(comment) => comments
+                    .push(comment.trim_start_matches(';').trim().to_string()),
+                None => { docs.insert(line, std::mem::take(&mut comments)); },
+            },
+        }
+    }
+    docs
+}
+
+/// Tells whether every bracket and string opened in `string` is closed.
+/// Brackets inside strings and comments are not counted.
+pub fn is_complete(string: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            ';' => chars.by_ref().take_while(|&c| c != '\n').for_each(drop),
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => { chars.next(); },
+                    Some(_) => {},
+                    None => return false,
+                }
+            },
+            _ => {},
+        }
+    }
+    depth <= 0
+}
+
+fn located<'a, T>(mut parser: impl FnMut(Input<'a>) -> MyResult<'a, T>)
+    -> impl FnMut(Input<'a>) -> MyResult<'a, (T, Span)>
+{
+    move |input| {
+        let span = span_of(&input);
+        let (rest, result) = parser(input)?;
+        Ok((rest, (result, span)))
+    }
+}
 
-pub fn parse(string: &str) -> Result<AST, nom::Err<Error<&str>>> {
-    let (_, result) = all_consuming(map(parse_many, AST::SExpr))(string)?;
-    Ok(result)
+fn span_of(input: &Input) -> Span {
+    input.extra.offset(input.location_line(), input.get_utf8_column())
 }
 
-fn parse_many(string: &str) -> MyResult<Vec<AST>> {
-    many0(parse_ast)(string)
+fn parse_many(string: Input) -> MyResult<Vec<AST>> {
+    preceded(spaces, many0(parse_ast))(string)
 }
 
-fn parse_ast(string: &str) -> MyResult {
+fn parse_ast(string: Input) -> MyResult {
     delimited(
         spaces,
         alt((
+            map(parse_bool, AST::Bool),
             map(parse_number, AST::Number),
-            map(parse_symbol, AST::Symbol),
-            map(parse_string, AST::Literal),
-            map(parse_sexpr, AST::SExpr),
+            map(located(parse_symbol),
+                |(sym, span)| AST::Symbol(sym.into(), span)),
+            map(parse_string, AST::from),
+            map(parse_sexpr, |(xs, span)| AST::SExpr(xs.into(), span)),
             map(parse_qexpr, AST::QExpr),
+            map(parse_map, AST::Map),
+            map(parse_quoted, |(xs, span)| AST::SExpr(xs.into(), span)),
         )),
         spaces
     )(string)
 }
 
-fn parse_number(string: &str) -> MyResult<i128> {
+/// `#t` or `#f`, which must not run on into a symbol, as in `#tx`.
+fn parse_bool(string: Input) -> MyResult<bool> {
+    let pattern = alt((value(true, tag("#t")), value(false, tag("#f"))));
+    let symbol_char = satisfy(|c| c.is_ascii_alphanumeric() || c == '#'
+                              || SYMBOL_CHARS.contains(c));
+    terminated(pattern, not(symbol_char))(string)
+}
+
+fn parse_number(string: Input) -> MyResult<Number> {
+    alt((
+        map(parse_float, Number::Float),
+        map(parse_ratio, Number::ratio),
+        map(parse_integer, Number::integer),
+    ))(string)
+}
+
+fn parse_float(string: Input) -> MyResult<f64> {
+    let exponent = || tuple((one_of("eE"), opt(one_of("+-")), digit1));
+    let pattern = tuple((
+        opt(tag("-")),
+        digit1,
+        alt((
+            recognize(pair(preceded(tag("."), digit1), opt(exponent()))),
+            recognize(exponent()),
+        )),
+    ));
+    map_res(recognize(pattern), |x: Input| x.fragment().parse())(string)
+}
+
+fn parse_ratio(string: Input) -> MyResult<BigRational> {
+    let pattern = separated_pair(parse_integer, tag("/"), parse_natural);
+    map_res(pattern, |(numer, denom)| match denom {
+        denom if denom == BigInt::from(0) => Err("zero denominator"),
+        denom => Ok(BigRational::new(numer, denom)),
+    })(string)
+}
+
+fn parse_integer(string: Input) -> MyResult<BigInt> {
     let pattern = preceded(opt(tag("-")), digit1);
-    map_res(recognize(pattern), FromStr::from_str)(string)
+    map_res(recognize(pattern), |x: Input| x.fragment().parse())(string)
+}
+
+fn parse_natural(string: Input) -> MyResult<BigInt> {
+    map_res(digit1, |x: Input| x.fragment().parse())(string)
 }
 
-fn parse_symbol(string: &str) -> MyResult<String> {
-    let pattern = many1(alt((
+/// What symbols are made of besides letters and digits.
+const SYMBOL_CHARS: &str = "_+-*/\\=<>!&|";
+
+/// Symbols may end in `#`, which marks names to be renamed in macro templates.
+fn parse_symbol(string: Input) -> MyResult<String> {
+    let pattern = pair(many1(alt((
         value((), alphanumeric1),
-        value((), one_of("_+-*/\\=<>!&|")),
-    )));
-    map(recognize(pattern), String::from)(string)
+        value((), one_of(SYMBOL_CHARS)),
+    ))), opt(tag("#")));
+    map(recognize(pattern), |x: Input| x.fragment().to_string())(string)
 }
 
-fn parse_string(string: &str) -> MyResult<String> {
-    let pattern = many1(alt((
+fn parse_string(string: Input) -> MyResult<String> {
+    let pattern = many0(alt((
         value('\n', tag("\\n")),
         value('\r', tag("\\r")),
-        value('\t', tag("\\r")),
+        value('\t', tag("\\t")),
         preceded(tag("\\"), anychar),
         none_of("\""),
     )));
@@ -61,18 +230,46 @@ fn parse_string(string: &str) -> MyResult<String> {
         (string)
 }
 
-fn parse_sexpr(string: &str) -> MyResult<Vec<AST>> {
-    delimited(tag("("), parse_many, tag(")"))(string)
+fn parse_sexpr(string: Input) -> MyResult<(Vec<AST>, Span)> {
+    located(delimited(tag("("), parse_many, tag(")")))(string)
+}
+
+fn parse_qexpr(string: Input) -> MyResult<QExpr> {
+    let pattern = located(delimited(tag("{"), parse_many, tag("}")));
+    map(pattern, |(xs, span)| QExpr::new(xs, span))(string)
+}
+
+/// `` `x ``, `,x` and `,@x` are read as `(quasiquote x)`, `(unquote x)` and
+/// `(unquote-splicing x)`.
+fn parse_quoted(string: Input) -> MyResult<(Vec<AST>, Span)> {
+    let marker = alt((
+        value("quasiquote", tag("`")),
+        value("unquote-splicing", tag(",@")),
+        value("unquote", tag(",")),
+    ));
+    let pattern = located(pair(marker, parse_ast));
+    map(pattern, |((name, ast), span)|
+        (vec![AST::Symbol(name.into(), span.clone()), ast], span))(string)
 }
 
-fn parse_qexpr(string: &str) -> MyResult<QExpr> {
-    into(delimited(tag("{"), parse_many, tag("}")))(string)
+/// `#{key value ...}`, whose contents are not evaluated, like a Q-expr's.
+fn parse_map(string: Input) -> MyResult<Map> {
+    let pattern = delimited(tag("#{"), parse_many, tag("}"));
+    map_res(pattern, |xs| {
+        if !xs.len().is_multiple_of(2) {
+            return Err(EvalError::Message("map without a value for a key"));
+        }
+        let mut xs = xs.into_iter();
+        std::iter::from_fn(|| Some((xs.next()?, xs.next()?)))
+            .map(|(key, value)| Ok((key.key()?, value)))
+            .collect()
+    })(string)
 }
 
-fn spaces(string: &str) -> MyResult<Option<&str>> {
-    delimited(multispace0, opt(comments), multispace0)(string)
+fn spaces(string: Input) -> MyResult<Input> {
+    recognize(many0(alt((multispace1, comments))))(string)
 }
 
-fn comments(string: &str) -> MyResult<&str> {
+fn comments(string: Input) -> MyResult<Input> {
     preceded(tag(";"), not_line_ending)(string)
 }
@@ -0,0 +1,300 @@
+//! A profiler telling how often each function is called, how long it runs
+//! and how much it allocates.
+//!
+//! While a profile is taken, every run of a lambda body and every call of a
+//! builtin is a frame. A function is charged the time from entering to
+//! leaving its frames, counted once for recursive calls, and apart from that
+//! the time spent outside the functions it called. Lambdas are told apart by
+//! their code and labelled with the name they were first bound to, builtins
+//! by the name they were registered under.
+//!
+//! Allocations are counted by [`CountingAlloc`], which the program has to
+//! install as its global allocator, or none are seen. It only counts while
+//! a profile is being taken, so installing it costs a load of an atomic per
+//! allocation otherwise. Values are built of
+//! reference-counted nodes and vectors, so what a function allocates is
+//! mostly the AST nodes it makes. Arithmetic that the compiler inlines is
+//! not a call and is not counted.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use super::{ast::AST, env::{Env, EnvObj}, function::Lambda};
+
+/// The name of the lambdas that were never bound to one.
+const LAMBDA: &str = "<lambda>";
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// How many profilers exist, in any session.
+static PROFILERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting the allocations made through it while a
+/// [`Profiler`] exists.
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn count() {
+        if PROFILERS.load(Ordering::Relaxed) > 0 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        CountingAlloc::count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize)
+        -> *mut u8
+    {
+        CountingAlloc::count();
+        System.realloc(ptr, layout, size)
+    }
+}
+
+/// How many allocations [`CountingAlloc`] has made so far.
+pub fn allocations() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Keeps [`CountingAlloc`] counting for as long as it lives.
+struct Counting;
+
+impl Default for Counting {
+    fn default() -> Self {
+        PROFILERS.fetch_add(1, Ordering::Relaxed);
+        Counting
+    }
+}
+
+impl Drop for Counting {
+    fn drop(&mut self) {
+        PROFILERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// What a frame runs: a lambda, or a builtin by its name.
+#[derive(Clone, Copy, Debug)]
+pub enum Callee<'a> {
+    Lambda(&'a Lambda),
+    Builtin(&'a str),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub name: String,
+    pub calls: usize,
+    /// The time from entering to leaving the outermost frames.
+    pub total: Duration,
+    /// The time spent outside the functions called.
+    pub own: Duration,
+    /// The allocations made outside the functions called.
+    pub allocations: usize,
+    /// How many frames of the function are open.
+    active: usize,
+}
+
+struct Open {
+    function: usize,
+    start: Instant,
+    /// The allocations counted when the frame was entered.
+    allocations: usize,
+    /// What the frames entered from this one took.
+    callees: Duration,
+    callee_allocations: usize,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    /// The lambdas by the address of their code, see [`Lambda::code`]. The
+    /// code is kept so that no other lambda takes the address.
+    lambda_ids: HashMap<usize, (usize, AST)>,
+    builtin_ids: HashMap<String, usize>,
+    functions: Vec<Stats>,
+    open: Vec<Open>,
+    /// The functions of the open frames, outermost first.
+    stack: Vec<usize>,
+    /// The time spent by each stack outside the functions it called.
+    stacks: HashMap<Vec<usize>, Duration>,
+    /// The allocations made by the profiler itself, left out of the counts.
+    overhead: usize,
+    _counting: Counting,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, callee: Callee) {
+        let before = allocations();
+        let function = self.id(callee);
+        self.functions[function].active += 1;
+        self.stack.push(function);
+        self.open.push(Open {
+            function,
+            start: Instant::now(),
+            allocations: 0,
+            callees: Duration::ZERO,
+            callee_allocations: 0,
+        });
+        self.overhead += allocations() - before;
+        let counted = self.counted();
+        let frame = self.open.last_mut().unwrap();
+        frame.allocations = counted;
+        frame.start = Instant::now();
+    }
+
+    /// Leaves the innermost frame, if any.
+    pub fn leave(&mut self) {
+        let (now, counted, before) =
+            (Instant::now(), self.counted(), allocations());
+        let frame = match self.open.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = now - frame.start;
+        let allocated = counted - frame.allocations;
+        let own = elapsed.saturating_sub(frame.callees);
+        let stats = &mut self.functions[frame.function];
+        stats.calls += 1;
+        stats.active -= 1;
+        if stats.active == 0 {
+            stats.total += elapsed;
+        }
+        stats.own += own;
+        stats.allocations += allocated.saturating_sub(frame.callee_allocations);
+        match self.stacks.get_mut(&self.stack[..]) {
+            Some(time) => *time += own,
+            None => { self.stacks.insert(self.stack.clone(), own); },
+        }
+        self.stack.pop();
+        if let Some(caller) = self.open.last_mut() {
+            caller.callees += elapsed;
+            caller.callee_allocations += allocated;
+        }
+        self.overhead += allocations() - before;
+    }
+
+    /// The functions called, the one that took the most time by itself
+    /// first.
+    pub fn functions(&self) -> Vec<&Stats> {
+        let mut functions = self.functions.iter().collect::<Vec<_>>();
+        functions.sort_by(|x, y| y.own.cmp(&x.own).then(x.name.cmp(&y.name)));
+        functions
+    }
+
+    /// A table of [`Profiler::functions`].
+    pub fn report(&self) -> String {
+        let mut report = format!("{:<24} {:>8} {:>10} {:>10} {:>10}\n",
+                                 "function", "calls", "total ms", "self ms",
+                                 "allocs");
+        for stats in self.functions() {
+            report += &format!("{:<24} {:>8} {:>10.3} {:>10.3} {:>10}\n",
+                               stats.name, stats.calls, millis(stats.total),
+                               millis(stats.own), stats.allocations);
+        }
+        report
+    }
+
+    /// The stacks in the folded format of flamegraph tools: one per line,
+    /// the functions from the outermost separated by `;`, then the
+    /// microseconds spent there outside the functions called.
+    pub fn folded(&self) -> String {
+        let mut lines = self.stacks.iter().map(|(stack, time)| {
+            let names = stack.iter()
+                .map(|&function| &*self.functions[function].name)
+                .collect::<Vec<_>>();
+            format!("{} {}\n", names.join(";"), time.as_micros())
+        }).collect::<Vec<_>>();
+        lines.sort();
+        lines.concat()
+    }
+
+    fn id(&mut self, callee: Callee) -> usize {
+        let found = match callee {
+            Callee::Lambda(lambda) =>
+                self.lambda_ids.get(&address(lambda)).map(|(id, _)| id),
+            Callee::Builtin(name) => self.builtin_ids.get(name),
+        };
+        if let Some(&id) = found {
+            return id;
+        }
+        let id = self.functions.len();
+        let name = match callee {
+            Callee::Lambda(lambda) => {
+                let code = lambda.code().clone();
+                self.lambda_ids.insert(address(lambda), (id, code));
+                lambda.name().unwrap_or(LAMBDA)
+            },
+            Callee::Builtin(name) => {
+                self.builtin_ids.insert(name.to_string(), id);
+                name
+            },
+        };
+        self.functions.push(Stats {
+            name: name.to_string(),
+            ..Stats::default()
+        });
+        id
+    }
+
+    /// The allocations made so far, but for the profiler's own.
+    fn counted(&self) -> usize {
+        allocations() - self.overhead
+    }
+}
+
+fn address(lambda: &Lambda) -> usize {
+    match lambda.code() {
+        AST::SExpr(code, _) => Rc::as_ptr(code) as *const AST as usize,
+        _ => unreachable!("the code of a lambda is an S-expression"),
+    }
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+/// Starts taking a profile in `env`, unless one is being taken there or it
+/// has nowhere to keep one. Tells whether it did.
+pub fn start(env: EnvObj) -> bool {
+    match env.profiler() {
+        Some(slot) if slot.is_none() => {
+            *slot = Some(Profiler::default());
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Stops taking the profile of `env`, giving it.
+pub fn finish(env: EnvObj) -> Option<Profiler> {
+    env.profiler().and_then(Option::take)
+}
+
+/// Runs `f` in a frame of `callee`, if a profile is being taken.
+pub fn frame<T>(env: EnvObj, callee: Callee, f: impl FnOnce(EnvObj) -> T)
+    -> T
+{
+    if !matches!(env.profiler(), Some(Some(_))) {
+        return f(env);
+    }
+    with_profiler(env, |profiler| profiler.enter(callee));
+    let result = f(env);
+    with_profiler(env, Profiler::leave);
+    result
+}
+
+fn with_profiler(env: &mut dyn Env, f: impl FnOnce(&mut Profiler)) {
+    if let Some(Some(profiler)) = env.profiler() {
+        f(profiler);
+    }
+}
@@ -1,61 +1,119 @@
-use std::{borrow::Borrow, iter::FromIterator};
-use super::{ast::AST, env::EnvObj, eval_error::*, transformers::sequence};
+use std::{fmt, iter::FromIterator};
+use im_rc::Vector;
+use super::{
+    ast::AST, env::EnvObj, eval_error::*, span::Span, transformers::sequence,
+};
 
+/// A quoted list. Its elements live in a persistent vector whose nodes are
+/// shared between copies, so cloning, `head`, `tail` and `cons` take constant
+/// time and `join` takes logarithmic time.
 #[derive(Clone, Debug, PartialEq)]
-pub struct QExpr(Vec<AST>);
+pub struct QExpr(Vector<AST>, Span);
 
 impl From<Vec<AST>> for QExpr {
     fn from(vec: Vec<AST>) -> Self {
-        Self(vec)
+        Self(Vector::from(vec), Span::default())
     }
 }
 
 impl FromIterator<AST> for QExpr {
     fn from_iter<I>(iter: I) -> Self where I: IntoIterator<Item = AST> {
-        Self::from(iter.into_iter().collect::<Vec<_>>())
+        Self(iter.into_iter().collect(), Span::default())
     }
 }
 
 impl QExpr {
+    pub fn new(asts: Vec<AST>, span: Span) -> Self {
+        Self(Vector::from(asts), span)
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.1
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AST> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&AST> {
+        self.0.get(index)
+    }
+
+    /// The first `index` elements and the rest, both in logarithmic time.
+    pub fn split_at(self, index: usize) -> (Self, Self) {
+        let index = index.min(self.len());
+        let (left, right) = self.0.split_at(index);
+        (Self(left, self.1.clone()), Self(right, self.1))
+    }
+
     pub fn head(self) -> EvalResult<QExpr> {
-        match self.0.into_iter().next() {
-            Some(head) => Ok(QExpr(vec![head])),
+        match self.0.front() {
+            Some(head) => Ok(QExpr(Vector::unit(head.clone()), self.1)),
             None => Err(EMPTY_QEXPR),
         }
     }
 
     pub fn tail(mut self) -> EvalResult<QExpr> {
-        if self.0.is_empty() {
-            return Err(EMPTY_QEXPR);
+        match self.0.pop_front() {
+            Some(_) => Ok(self),
+            None => Err(EMPTY_QEXPR),
         }
-        self.0.reverse();
-        self.0.pop();
-        self.0.reverse();
-        Ok(self)
+    }
+
+    pub fn cons(mut self, head: AST) -> Self {
+        self.0.push_front(head);
+        self
+    }
+
+    pub fn join(mut self, other: QExpr) -> Self {
+        self.0.append(other.0);
+        self
+    }
+
+    /// Applies `f` to every element, keeping the span.
+    pub fn try_map(self, f: impl FnMut(AST) -> EvalResult) -> EvalResult<Self> {
+        Ok(Self(sequence(self.0, f)?.into(), self.1))
     }
 
     pub fn symbols(self) -> EvalResult<Vec<String>> {
-        sequence(self.0.into_iter(), AST::symbol)
+        sequence(self.0, AST::symbol)
     }
 
     pub fn eval(self, env: EnvObj) -> EvalResult {
-        AST::SExpr(self.0).eval(env)
+        self.sexpr().eval(env)
+    }
+
+    pub fn sexpr(self) -> AST {
+        AST::SExpr(self.0.into_iter().collect(), self.1)
     }
 }
 
-impl Borrow<[AST]> for QExpr {
-    fn borrow(&self) -> &[AST] {
-        self.0.borrow()
+impl IntoIterator for QExpr {
+    type Item = AST;
+    type IntoIter = im_rc::vector::ConsumingIter<AST>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
     }
 }
 
-impl ToString for QExpr {
-    fn to_string(&self) -> String {
-        pprint("{", &self.0, "}")
+impl fmt::Display for QExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&pprint("{", self.iter(), "}"))
     }
 }
 
-pub fn pprint(before: &str, asts: &Vec<AST>, after: &str) -> String {
-    let strings = asts.iter().map(AST::to_string).collect::<Vec<_>>();
+pub fn pprint<'a>(before: &str,
+                  asts: impl IntoIterator<Item = &'a AST>,
+                  after: &str) -> String {
+    let strings = asts.into_iter().map(AST::to_string).collect::<Vec<_>>();
     before.to_owned() + &strings.join(" ") + after
 }
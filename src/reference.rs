@@ -0,0 +1,78 @@
+use std::{cell::RefCell, collections::HashSet, fmt, rc::{Rc, Weak}};
+use super::ast::AST;
+
+/// A mutable cell. Copies of a reference share the cell, so a closure that
+/// captures one sees every update made through the others.
+#[derive(Clone, Debug)]
+pub struct Ref(Rc<RefCell<AST>>);
+
+/// A reference that does not keep its cell alive, see [`crate::heap`].
+pub struct WeakRef(Weak<RefCell<AST>>);
+
+impl Ref {
+    pub fn new(value: AST) -> Self {
+        Self(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn get(&self) -> AST {
+        self.0.borrow().clone()
+    }
+
+    pub fn set(&self, value: AST) {
+        *self.0.borrow_mut() = value;
+    }
+
+    /// The value in place, which cannot be set until it is dropped.
+    pub fn peek(&self) -> std::cell::Ref<'_, AST> {
+        self.0.borrow()
+    }
+
+    /// How many copies of this reference there are.
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    /// The same for every copy of this reference, and only for those.
+    pub fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    pub fn downgrade(&self) -> WeakRef {
+        WeakRef(Rc::downgrade(&self.0))
+    }
+}
+
+impl WeakRef {
+    pub fn upgrade(&self) -> Option<Ref> {
+        self.0.upgrade().map(Ref)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+}
+
+/// References are equal only to themselves, whatever they hold.
+impl PartialEq for Ref {
+    fn eq(&self, other: &Ref) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+thread_local! {
+    /// The cells being printed, so that a cell holding itself is printed
+    /// once rather than forever.
+    static PRINTING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+impl fmt::Display for Ref {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self.id();
+        if !PRINTING.with(|cells| cells.borrow_mut().insert(id)) {
+            return f.write_str("<ref ...>");
+        }
+        let result = write!(f, "<ref {}>", self.0.borrow());
+        PRINTING.with(|cells| cells.borrow_mut().remove(&id));
+        result
+    }
+}
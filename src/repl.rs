@@ -0,0 +1,156 @@
+//! The commands the REPL reads besides Lisp, on lines starting with `:`:
+//!
+//! - `:env` lists the global bindings, `:doc sym` tells what `sym` is and
+//!   `:source sym` shows the lambda bound to it;
+//! - `:type expr` gives the type of the value of `expr`, `:time expr` that
+//!   value and how long it took;
+//! - `:load file` evaluates a file, `:reset` forgets every definition and
+//!   `:quit` ends the session.
+//!
+//! Other lines starting with `:` are left to the [`crate::debugger`].
+
+use std::{iter, time::Instant};
+use super::{
+    Error, Interpreter,
+    ast::AST,
+    env::{Env, PRELUDE, PRELUDE_FILE},
+    eval_error::EvalError,
+    function::Function,
+    parser::doc_comments,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Env,
+    Doc(String),
+    Source(String),
+    Type(String),
+    Reset,
+    Load(String),
+    Time(String),
+    Quit,
+}
+
+impl Command {
+    /// The command written on `line`, `None` if it is not one of these, or
+    /// how to use it if its arguments are wrong.
+    pub fn parse(line: &str) -> Option<Result<Command, String>> {
+        let line = line.trim().strip_prefix(':')?;
+        let (name, rest) = match line.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (line, ""),
+        };
+        let none = rest.is_empty();
+        let one = rest.split_whitespace().count() == 1;
+        let command = match name {
+            "env" if none => Command::Env,
+            "doc" if one => Command::Doc(rest.into()),
+            "source" if one => Command::Source(rest.into()),
+            "type" if !none => Command::Type(rest.into()),
+            "reset" if none => Command::Reset,
+            "load" if !none => Command::Load(rest.into()),
+            "time" if !none => Command::Time(rest.into()),
+            "quit" if none => Command::Quit,
+            "env" | "reset" | "quit" => return usage(name, ""),
+            "doc" | "source" => return usage(name, " symbol"),
+            "type" | "time" => return usage(name, " expr"),
+            "load" => return usage(name, " file"),
+            _ => return None,
+        };
+        Some(Ok(command))
+    }
+
+    /// Runs the command in `lisp`, giving what to show. Ending the session
+    /// on `:quit` is left to the caller.
+    pub fn run(self, lisp: &mut Interpreter) -> Result<String, Error> {
+        match self {
+            Command::Env => {
+                let bindings = lisp.global().bindings().into_iter()
+                    .map(|(name, value)|
+                         format!("{:<20} {}", name, describe(&value)));
+                Ok(bindings.collect::<Vec<_>>().join("\n"))
+            },
+            Command::Doc(name) => {
+                let value = lookup(lisp, &name)?;
+                Ok(doc(lisp, &name, &value))
+            },
+            Command::Source(name) => match lookup(lisp, &name)? {
+                AST::Function(Function::Lambda(f) | Function::Macro(f)) =>
+                    Ok(f.to_string()),
+                value => Ok(format!("{}: {}", name, describe(&value))),
+            },
+            Command::Type(source) => Ok(describe(&lisp.eval(&source)?)),
+            Command::Reset => {
+                lisp.reset();
+                Ok("every definition was forgotten".into())
+            },
+            Command::Load(file) => {
+                lisp.load(&file)?;
+                Ok(format!("loaded {}", file))
+            },
+            Command::Time(source) => {
+                let start = Instant::now();
+                let value = lisp.eval(&source)?;
+                let millis = start.elapsed().as_secs_f64() * 1000.0;
+                Ok(format!("{}\ntime: {:.3} ms", value, millis))
+            },
+            Command::Quit => Ok(String::new()),
+        }
+    }
+}
+
+fn usage(name: &str, args: &str) -> Option<Result<Command, String>> {
+    Some(Err(format!("usage: :{}{}", name, args)))
+}
+
+fn lookup(lisp: &Interpreter, name: &str) -> Result<AST, Error> {
+    lisp.get(name)
+        .ok_or_else(|| Error::Eval(EvalError::UnknownVar(name.into())))
+}
+
+/// The type of `value`, with the parameters of lambdas.
+fn describe(value: &AST) -> String {
+    match value {
+        AST::Function(Function::Builtin(..) | Function::Tail(..)) =>
+            "builtin function".into(),
+        AST::Function(Function::Special(..)) => "special form".into(),
+        AST::Function(Function::Lambda(f)) =>
+            format!("lambda {{{}}}", f.params().join(" ")),
+        AST::Function(Function::Macro(f)) =>
+            format!("macro {{{}}}", f.params().join(" ")),
+        value => value.typ().into(),
+    }
+}
+
+/// How to call the lambda bound to `name`, the comments above its
+/// definition and where that is.
+fn doc(lisp: &mut Interpreter, name: &str, value: &AST) -> String {
+    let lambda = match value {
+        AST::Function(Function::Lambda(f) | Function::Macro(f)) => f,
+        value => return format!("{}: {}", name, describe(value)),
+    };
+    let call = iter::once(name).chain(lambda.params()).collect::<Vec<_>>();
+    let mut doc = vec![format!("({})", call.join(" "))];
+    let span = lambda.span();
+    if let Some(file) = &span.file {
+        doc.extend(comments(lisp, file, span.line));
+        doc.push(format!("defined at {}", span));
+    }
+    doc.join("\n")
+}
+
+/// The comment lines just above the top-level form that `line` of `file`
+/// is part of, as they were when the file was loaded.
+fn comments(lisp: &mut Interpreter, file: &str, line: u32) -> Vec<String> {
+    let prelude;
+    let docs = match file == PRELUDE_FILE {
+        true => {
+            prelude = doc_comments(PRELUDE);
+            Some(&prelude)
+        },
+        false => lisp.global().docs().and_then(|docs| docs.get(file)),
+    };
+    docs.and_then(|docs| docs.range(..=line).next_back())
+        .map(|(_, comments)| comments.clone())
+        .unwrap_or_default()
+}
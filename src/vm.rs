@@ -0,0 +1,239 @@
+//! The stack machine running the bytecode of [`crate::compiler`].
+
+use super::{
+    ast::{AST, Step},
+    compiler::{Chunk, Op},
+    debugger::Debugger,
+    env::{Bindings, Docs, Env, EnvObj, shadow},
+    eval_error::*,
+    function::Function,
+    heap::Heap,
+    number::Number,
+    profiler::Profiler,
+    qexpr::QExpr,
+};
+
+/// Runs `chunk` with its slots filled, up to a value or a lambda to apply
+/// next. The slots are left as the code leaves them, for backtraces.
+pub fn run(chunk: &Chunk, slots: &mut Vec<AST>, env: EnvObj)
+    -> EvalResult<Step>
+{
+    let mut frame = Activation {
+        names: &chunk.locals,
+        slots,
+        extra: Bindings::new(),
+        parent: env.global(),
+    };
+    let mut stack = Vec::with_capacity(chunk.stack);
+    let mut pc = 0;
+    loop {
+        let op = chunk.ops[pc];
+        let next = frame.exec(chunk, op, &mut stack)
+            .map_err(|err| err.at(&chunk.spans[pc]))?;
+        pc = match next {
+            Next::Op => pc + 1,
+            Next::Jump(target) => target,
+            Next::Return(step) => return Ok(step),
+        };
+    }
+}
+
+enum Next {
+    Op,
+    Jump(usize),
+    Return(Step),
+}
+
+/// The scope of a compiled call: slots first, then whatever `=` added.
+struct Activation<'a> {
+    names: &'a [String],
+    slots: &'a mut Vec<AST>,
+    extra: Bindings,
+    parent: EnvObj<'a>,
+}
+
+impl<'a> Activation<'a> {
+    fn exec(&mut self, chunk: &Chunk, op: Op, stack: &mut Vec<AST>)
+        -> EvalResult<Next>
+    {
+        match op {
+            Op::Const(i) => stack.push(chunk.constants[i].clone()),
+            Op::Local(i) => stack.push(self.slots[i].clone()),
+            Op::Global(i) => stack.push(self.global_value(chunk, i)?),
+            Op::Call(argc) => {
+                let (fun, args) = pop_call(stack, argc)?;
+                stack.push(fun.call(self, args.into_iter())?);
+            },
+            Op::TailCall(argc) => {
+                let (fun, args) = pop_call(stack, argc)?;
+                let step = fun.step(self, args.into_iter())?;
+                return self.settle(step);
+            },
+            Op::Prim(prim, global, builtin) => {
+                let (y, x) = (pop(stack), pop(stack));
+                let fun = self.global_value(chunk, global)?;
+                let inline = match (&x, &y) {
+                    (AST::Number(Number::Int(x)), AST::Number(Number::Int(y)))
+                        if fun == chunk.constants[builtin] => prim.ints(*x, *y),
+                    _ => None,
+                };
+                stack.push(match inline {
+                    Some(value) => value,
+                    None => fun.function()?.call(self, vec![x, y].into_iter())?,
+                });
+            },
+            Op::Jump(target) => return Ok(Next::Jump(target)),
+            Op::JumpIfFalse(target) => if !pop(stack).is_truthy() {
+                return Ok(Next::Jump(target));
+            },
+            Op::JumpIfFalseOrPop(target) => match top(stack).is_truthy() {
+                true => { pop(stack); },
+                false => return Ok(Next::Jump(target)),
+            },
+            Op::JumpIfTrueOrPop(target) => match top(stack).is_truthy() {
+                true => return Ok(Next::Jump(target)),
+                false => { pop(stack); },
+            },
+            Op::Pop => { pop(stack); },
+            Op::Return => return Ok(Next::Return(Step::Done(pop(stack)))),
+            Op::Eval(i) => stack.push(chunk.constants[i].clone().eval(self)?),
+            Op::TailEval(i) =>
+                return self.settle(Step::Eval(chunk.constants[i].clone())),
+        }
+        Ok(Next::Op)
+    }
+
+    /// The value of the `i`th global of `chunk`, unless `=` bound its name
+    /// in this call. It is cached until a global binding changes.
+    fn global_value(&self, chunk: &Chunk, i: usize) -> EvalResult<AST> {
+        if let Some(value) = self.extra.get(&*chunk.globals[i]) {
+            return Ok(value.clone());
+        }
+        let generation = self.parent.generation();
+        if let (Some(now), Some((then, value))) =
+            (generation, &chunk.cache.borrow()[i])
+        {
+            if now == *then {
+                return Ok(value.clone());
+            }
+        }
+        let value = self.parent.get(&chunk.globals[i])?;
+        if let Some(now) = generation {
+            chunk.cache.borrow_mut()[i] = Some((now, value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// Code to evaluate is evaluated here, where its variables are bound.
+    fn settle(&mut self, step: Step) -> EvalResult<Next> {
+        match step {
+            Step::Eval(ast) => ast.eval_tail(self).map(Next::Return),
+            step => Ok(Next::Return(step)),
+        }
+    }
+
+    fn slot(&self, key: &str) -> Option<usize> {
+        self.names.iter().position(|name| name == key)
+    }
+}
+
+fn pop_call(stack: &mut Vec<AST>, argc: usize)
+    -> EvalResult<(Function, Vec<AST>)>
+{
+    let args = stack.split_off(stack.len() - argc);
+    Ok((pop(stack).function()?, args))
+}
+
+fn pop(stack: &mut Vec<AST>) -> AST {
+    stack.pop().expect("compiled code keeps the stack balanced")
+}
+
+fn top(stack: &[AST]) -> &AST {
+    stack.last().expect("compiled code keeps the stack balanced")
+}
+
+impl<'a> Env for Activation<'a> {
+    fn get(&self, key: &str) -> EvalResult<AST> {
+        match self.local(key) {
+            Some(value) => Ok(value),
+            None => self.parent.get(key),
+        }
+    }
+
+    fn local(&self, key: &str) -> Option<AST> {
+        match self.slot(key) {
+            Some(i) => Some(self.slots[i].clone()),
+            None => self.extra.get(key).cloned(),
+        }
+    }
+
+    fn global(&mut self) -> EnvObj<'_> {
+        self.parent.global()
+    }
+
+    fn define(&mut self, bindings: Vec<(String, AST)>) {
+        self.parent.define(bindings);
+    }
+
+    fn assign(&mut self, bindings: Vec<(String, AST)>) {
+        for (name, value) in bindings {
+            match self.slot(&name) {
+                Some(i) => self.slots[i] = value,
+                None => { self.extra.insert(name, value); },
+            }
+        }
+    }
+
+    fn compiling(&self) -> bool {
+        self.parent.compiling()
+    }
+
+    fn generation(&self) -> Option<usize> {
+        self.parent.generation()
+    }
+
+    fn heap(&mut self) -> Option<&mut Heap> {
+        self.parent.heap()
+    }
+
+    fn debugger(&mut self) -> Option<&mut Option<Debugger>> {
+        self.parent.debugger()
+    }
+
+    fn debugging(&self) -> bool {
+        self.parent.debugging()
+    }
+
+    fn depth(&mut self) -> Option<&mut usize> {
+        self.parent.depth()
+    }
+
+    fn profiler(&mut self) -> Option<&mut Option<Profiler>> {
+        self.parent.profiler()
+    }
+
+    fn tests(&mut self) -> Option<&mut Vec<(String, QExpr)>> {
+        self.parent.tests()
+    }
+
+    fn docs(&mut self) -> Option<&mut Docs> {
+        self.parent.docs()
+    }
+
+    fn roots(&self) -> Vec<AST> {
+        let mut roots = self.parent.roots();
+        roots.extend(self.slots.iter().chain(self.extra.values()).cloned());
+        roots
+    }
+
+    fn locals(&self) -> Vec<(String, AST)> {
+        let slots = self.names.iter().cloned().zip(self.slots.iter().cloned());
+        let mut locals = slots.chain(self.extra.clone()).collect::<Vec<_>>();
+        locals.sort_by(|(x, _), (y, _)| x.cmp(y));
+        locals
+    }
+
+    fn bindings(&self) -> Vec<(String, AST)> {
+        shadow(self.parent.bindings(), self.locals())
+    }
+}
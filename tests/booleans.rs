@@ -0,0 +1,44 @@
+//! Booleans, distinct from numbers. Every value counts as true in a
+//! condition except `#f` and nil.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn literals() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "list #t #f"), "{#t #f}");
+    assert_eq!(eval(&mut lisp, "list (#t){#f}"), "{#t {#f}}");
+    assert_eq!(eval(&mut lisp, "list #t;comment"), "{#t}");
+    for source in ["#tx", "#true", "#f1", "#t-", "#t#", "#x"] {
+        assert_eq!(error(&mut lisp, source),
+                   format!("parse error: 1:1: cannot parse `{}`", source));
+    }
+}
+
+#[test]
+fn truthiness() {
+    let mut lisp = Interpreter::new();
+    let branch = |lisp: &mut Interpreter, test| {
+        eval(lisp, &format!("if {} {{\"yes\"}} {{\"no\"}}", test))
+    };
+    for test in ["#t", "0", "\"\"", "{0}", "#{}", "(ref #f)"] {
+        assert_eq!(branch(&mut lisp, test), "\"yes\"", "{}", test);
+    }
+    for test in ["#f", "{}", "nil"] {
+        assert_eq!(branch(&mut lisp, test), "\"no\"", "{}", test);
+    }
+}
+
+#[test]
+fn comparisons_give_booleans() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "list (< 1 2) (== 1 2) (! 0) (! #f)"),
+               "{#t #f #f #t}");
+    assert_eq!(eval(&mut lisp, "== #t #t"), "#t");
+    assert_eq!(eval(&mut lisp, "== #t 1"), "#f");
+    let err = lisp.eval("< 1 #t").unwrap_err().to_string();
+    assert!(err.ends_with("expected number, got bool"));
+}
@@ -0,0 +1,82 @@
+//! The `lispy` command line.
+
+use std::{io::Write, process::{Command, Stdio}};
+
+fn lispy(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_lispy"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn unknown_flags_are_rejected() {
+    let output = lispy(&["--bogus", "file.lispy"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("unknown flag --bogus\nusage: lispy "));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn profiles_need_files_to_run() {
+    for args in [&["--profile"][..], &["--profile=out", "test", "a.lispy"]] {
+        let output = lispy(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with("--profile only applies to running files\n\
+                                    usage: lispy "), "{}", stderr);
+    }
+}
+
+#[test]
+fn the_prelude_loads_quietly() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-cli-empty-{}.lispy", std::process::id()));
+    std::fs::write(&file, "").unwrap();
+    let output = lispy(&[file.to_str().unwrap()]);
+    std::fs::remove_file(&file).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
+
+#[test]
+fn tests_are_isolated() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-cli-{}.lispy", std::process::id()));
+    std::fs::write(&file, "print \"loading\"\n\
+                           def {log} (ref {})\n\
+                           deftest \"first\" {swap! log join {1}}\n\
+                           deftest \"second\" {assert-eq {} (deref log)}\n\
+                           deftest \"registry\" {*tests*}\n")
+        .unwrap();
+    let output = lispy(&["test", file.to_str().unwrap()]);
+    std::fs::remove_file(&file).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("loading").count(), 3, "{}", stdout);
+    assert!(stdout.contains("test result: FAILED. 2 passed; 1 failed"),
+            "{}", stdout);
+    assert!(stdout.contains("unknown variable: *tests*"), "{}", stdout);
+}
+
+#[test]
+fn the_repl_reads_forms_over_several_lines() {
+    let dir = std::env::temp_dir()
+        .join(format!("lispy-cli-repl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut repl = Command::new(env!("CARGO_BIN_EXE_lispy"))
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    repl.stdin.take().unwrap()
+        .write_all(b"(+ 1\n  2)\ndef {xs} {1 ; {\n\"}\" 3}\nxs\n")
+        .unwrap();
+    let output = repl.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),
+               "3\n()\n{1 \"}\" 3}\n");
+}
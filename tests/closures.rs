@@ -0,0 +1,49 @@
+//! Lambdas see the bindings of the scope they were made in, not those of
+//! their callers.
+
+mod common;
+
+use lispy::{Error, Interpreter};
+use common::eval;
+
+#[test]
+fn parameters_outlive_their_call() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {adder n} {\\ {x} {+ x n}}");
+    eval(&mut lisp, "def {add5} (adder 5)");
+    assert_eq!(eval(&mut lisp, "add5 1"), "6");
+    assert_eq!(eval(&mut lisp, "map (adder 1) {1 2 3}"), "{2 3 4}");
+    eval(&mut lisp, "fun {triple a} {\\ {b} {\\ {c} {list a b c}}}");
+    assert_eq!(eval(&mut lisp, "((triple 1) 2) 3"), "{1 2 3}");
+}
+
+#[test]
+fn callers_do_not_leak_in() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {adder n} {\\ {x} {+ x n}}");
+    eval(&mut lisp, "def {n} 100");
+    eval(&mut lisp, "fun {call f n} {f 1}");
+    assert_eq!(eval(&mut lisp, "call (adder 5) 1000"), "6");
+    eval(&mut lisp, "def {x} 1");
+    eval(&mut lisp, "fun {shadow x} {\\ {_} {x}}");
+    eval(&mut lisp, "= {x} 3");
+    assert_eq!(eval(&mut lisp, "(shadow 2) ()"), "2");
+}
+
+#[test]
+fn globals_are_looked_up_when_called() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {later _} {helper 2}");
+    eval(&mut lisp, "fun {helper x} {* x 10}");
+    assert_eq!(eval(&mut lisp, "later ()"), "20");
+    eval(&mut lisp, "fun {free _} {missing}");
+    match lisp.eval("free ()") {
+        Err(Error::Eval(err)) => {
+            assert_eq!(err.kind(), "unbound");
+            assert!(err.to_string().ends_with("unknown variable: missing"));
+        },
+        other => panic!("free variable resolved: {:?}", other),
+    }
+    eval(&mut lisp, "def {missing} 7");
+    assert_eq!(eval(&mut lisp, "free ()"), "7");
+}
@@ -0,0 +1,19 @@
+//! Helpers shared by the tests. Each test file uses some of them only.
+#![allow(dead_code)]
+
+use lispy::Interpreter;
+
+/// The value of `source`, printed. Failing to evaluate fails the test.
+pub fn eval(lisp: &mut Interpreter, source: &str) -> String {
+    lisp.eval(source)
+        .unwrap_or_else(|err| panic!("{}: {}", source, err))
+        .to_string()
+}
+
+/// The error `source` fails with, printed. A value fails the test.
+pub fn error(lisp: &mut Interpreter, source: &str) -> String {
+    match lisp.eval(source) {
+        Ok(value) => panic!("{} gave {}", source, value),
+        Err(err) => err.to_string(),
+    }
+}
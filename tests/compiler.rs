@@ -0,0 +1,101 @@
+//! Compiled lambdas checked against the tree-walking evaluator: every
+//! program must give the same value, or the same error, either way.
+
+use lispy::Interpreter;
+
+const DEFINITIONS: &str = r#"
+fun {add3 a b c} {+ a b c}
+
+fun {args x & rest} {list x rest}
+
+fun {adder n} {\ {x} {+ x n}}
+
+fun {sign x} {cond {(< x 0) "neg"} {(== x 0)} {else "pos"}}
+
+fun {logic x} {list (and x 1 2) (or #f x 3)}
+
+fun {assigned x} {do (= {x} 10) (= {y} 20) (+ x y)}
+
+fun {evaluated x} {eval {+ x 1}}
+
+fun {guarded x} {when (< x 3) (+ x 100)}
+
+fun {size x} {case x {1 "one"} {{2 3} "few"} {else "many"}}
+
+fun {countdown n} {if (== n 0) {"done"} {countdown (- n 1)}}
+
+fun {double x} {+ x x}
+
+fun {broken x} {+ x "a"}
+
+fun {unbound x} {+ x nope}
+
+fun {template x} {`(a ,x ,@{1 2})}
+
+fun {shadowed x} {do (= {+} -) (+ x 1)}
+"#;
+
+const PROGRAMS: &[&str] = &[
+    "list (add3 1 2 3) ((add3 1) 2 3) ((add3 1 2) 3)",
+    "list (args 1) (args 1 2 3)",
+    "(adder 5) 10",
+    "list (sign -1) (sign 0) (sign 3)",
+    "list (logic #t) (logic #f)",
+    "assigned 1",
+    "evaluated 41",
+    "list (guarded 1) (guarded 5)",
+    "list (size 1) (size 3) (size 9)",
+    "countdown 100000",
+    "double 170141183460469231731687303715884105727",
+    "double 0.25",
+    "broken 1",
+    "unbound 1",
+    "add3 1 2 3 4",
+    "template 5",
+    "shadowed 5",
+    "do (def {+} *) (double 3)",
+];
+
+fn eval_all(compile: bool) -> Vec<String> {
+    let mut lisp = Interpreter::new();
+    lisp.compile(compile);
+    for definition in DEFINITIONS.split("\n\n") {
+        lisp.eval(definition).unwrap();
+    }
+    PROGRAMS.iter()
+        .map(|program| match lisp.eval(program) {
+            Ok(value) => value.to_string(),
+            Err(err) => err.to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn same_results() {
+    let (tree, compiled) = (eval_all(false), eval_all(true));
+    let results = tree.into_iter().zip(compiled);
+    for (program, (expected, actual)) in PROGRAMS.iter().zip(results) {
+        assert_eq!(expected, actual, "{}", program);
+    }
+}
+
+#[test]
+fn compile_builtin() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {square x} {* x x}").unwrap();
+    lisp.eval("def {fast} (compile square)").unwrap();
+    assert_eq!(lisp.eval("fast 12").unwrap().to_string(), "144");
+    assert_eq!(lisp.eval("== fast square").unwrap().to_string(), "#t");
+    assert_eq!(lisp.eval("compile +").unwrap().to_string(), "<function>");
+}
+
+#[test]
+fn compiled_lambdas_define_tests() {
+    let mut lisp = Interpreter::new();
+    lisp.compile(true);
+    lisp.eval("fun {check name} {deftest name {assert-eq 2 (+ 1 1)}}")
+        .unwrap();
+    lisp.eval("check \"yes\"").unwrap();
+    assert_eq!(lisp.tests(), ["yes"]);
+    assert!(lisp.run_test("yes").is_ok());
+}
@@ -0,0 +1,69 @@
+//! `and`, `or`, `cond` and `case` evaluate only the arguments they need.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn junctions_short_circuit() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "and #f (nope)"), "#f");
+    assert_eq!(eval(&mut lisp, "or 1 (nope)"), "1");
+    assert_eq!(eval(&mut lisp, "and 1 2 3"), "3");
+    assert_eq!(eval(&mut lisp, "or #f {} 3"), "3");
+    assert_eq!(eval(&mut lisp, "or #f {}"), "{}");
+    assert!(error(&mut lisp, "and #t (nope)")
+        .ends_with("unknown variable: nope"));
+}
+
+#[test]
+fn cond_clauses() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "cond {#f 1} {(+ 1 1)} {else 3}"), "2");
+    assert_eq!(eval(&mut lisp, "cond {#f (nope)} {else 3}"), "3");
+    assert_eq!(eval(&mut lisp, "cond {#f 1}"), "{}");
+    assert_eq!(eval(&mut lisp, "cond {else}"), "#t");
+    eval(&mut lisp, "def {log} (ref {})");
+    let body = "cond {(== 1 1) (swap! log join {1}) (swap! log join {2}) 5}";
+    assert_eq!(eval(&mut lisp, body), "5");
+    assert_eq!(eval(&mut lisp, "deref log"), "{1 2}");
+}
+
+#[test]
+fn case_clauses() {
+    let mut lisp = Interpreter::new();
+    let size = "case {} {1 \"one\"} {{2 3} \"few\"} {else \"many\"}";
+    for (key, expected) in [("1", "one"), ("3", "few"), ("9", "many")] {
+        let source = size.replacen("{}", key, 1);
+        assert_eq!(eval(&mut lisp, &source), format!("\"{}\"", expected));
+    }
+    assert_eq!(eval(&mut lisp, "case 9 {1 \"one\"}"), "{}");
+    assert_eq!(eval(&mut lisp, "case (+ 1 1) {1 (nope)} {2 \"two\"}"),
+               "\"two\"");
+}
+
+#[test]
+fn bad_clauses() {
+    let mut lisp = Interpreter::new();
+    for source in ["cond 1", "case 1 2"] {
+        assert!(error(&mut lisp, source)
+            .ends_with("expected Q-expr, got number"));
+    }
+    assert!(error(&mut lisp, "cond {}").ends_with("qexpr is empty"));
+}
+
+#[test]
+fn inside_lambdas() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        eval(&mut lisp, "fun {sign n} {cond {(< n 0) -1} {(== n 0) 0} \
+                                             {else 1}}");
+        eval(&mut lisp, "fun {safe n} {and (!= n 0) (/ 1 n)}");
+        eval(&mut lisp, "fun {name n} {case n {1 \"one\"} {else (nope)}}");
+        assert_eq!(eval(&mut lisp, "map sign {-5 0 5}"), "{-1 0 1}");
+        assert_eq!(eval(&mut lisp, "map safe {0 2}"), "{#f 1/2}");
+        assert_eq!(eval(&mut lisp, "name 1"), "\"one\"");
+    }
+}
@@ -0,0 +1,107 @@
+//! Debugging sessions driven by a script instead of a terminal.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use lispy::{Console, Debugger, Interpreter};
+
+struct Script {
+    input: VecDeque<&'static str>,
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+impl Console for Script {
+    fn read_line(&mut self, _: &str) -> Option<String> {
+        self.input.pop_front().map(str::to_string)
+    }
+
+    fn print(&mut self, text: &str) {
+        self.output.borrow_mut().push(text.to_string());
+    }
+}
+
+const DEFINITIONS: &[&str] = &[
+    "fun {sq x} {* x x}",
+    "fun {sumsq a b} {+ (sq a) (sq b)}",
+];
+
+/// A session with the definitions, answering the debugger with `input`
+/// and showing what it finds in `output`.
+fn session(input: &[&'static str], output: &Rc<RefCell<Vec<String>>>)
+    -> Interpreter
+{
+    let mut lisp = Interpreter::new();
+    lisp.attach(Debugger::new(Box::new(Script {
+        input: input.iter().copied().collect(),
+        output: output.clone(),
+    })));
+    for definition in DEFINITIONS {
+        lisp.eval(definition).unwrap();
+    }
+    lisp
+}
+
+/// Evaluates `source` in a session answering the debugger with `input`,
+/// giving the value and what the debugger showed.
+fn debug(commands: &[&str], source: &str, input: &[&'static str])
+    -> (String, Vec<String>)
+{
+    let output = Rc::new(RefCell::new(vec![]));
+    let mut lisp = session(input, &output);
+    for command in commands {
+        assert!(lisp.debugger().unwrap().command(command));
+    }
+    let value = lisp.eval(source).unwrap().to_string();
+    lisp.debugger().unwrap().finish();
+    let output = output.borrow().clone();
+    (value, output)
+}
+
+#[test]
+fn breakpoint_shows_the_frame() {
+    let (value, output) = debug(&[":break sq"], "sumsq 3 4",
+        &[":locals", ":bt", "+ x 100", ":continue", ":continue"]);
+    assert_eq!(value, "25");
+    assert_eq!(output, vec![
+        "breakpoint sq:\n  (sq 3) defined at 1:12",
+        "x = 3",
+        "  (sq 3) defined at 1:12",
+        "  (sumsq 3 4) defined at 1:17",
+        "103",
+        "breakpoint sq:\n  (sq 4) defined at 1:12",
+    ]);
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let (value, output) = debug(&[":step"], "sumsq 1 2",
+        &[":step", ":step", ":next", ":next", ":continue"]);
+    assert_eq!(value, "5");
+    assert_eq!(output, vec![
+        "1:1: (sumsq 1 2)",
+        "1:17: (+ (sq a) (sq b))",
+        "1:20: (sq a)",
+        "1:27: (sq b)",
+    ]);
+}
+
+#[test]
+fn not_a_command() {
+    let mut lisp = Interpreter::new();
+    lisp.attach(Debugger::new(Box::new(Script {
+        input: VecDeque::new(),
+        output: Rc::new(RefCell::new(vec![])),
+    })));
+    assert!(!lisp.debugger().unwrap().command("+ 1 2"));
+    assert_eq!(lisp.eval("+ 1 2").unwrap().to_string(), "3");
+}
+
+#[test]
+fn sessions_are_debugged_apart() {
+    let output = Rc::new(RefCell::new(vec![]));
+    let mut stopping = session(&[":continue"], &output);
+    assert!(stopping.debugger().unwrap().command(":break sq"));
+    let mut other = session(&[], &output);
+    assert_eq!(other.eval("sq 3").unwrap().to_string(), "9");
+    other.debugger().unwrap().finish();
+    assert_eq!(stopping.eval("sq 2").unwrap().to_string(), "4");
+    assert_eq!(*output.borrow(), ["breakpoint sq:\n  (sq 2) defined at 1:12"]);
+}
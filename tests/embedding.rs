@@ -0,0 +1,31 @@
+//! A host program drives the interpreter through the types exported at the
+//! root of the crate, and gives it builtins of its own.
+
+mod common;
+
+use std::{cell::Cell, rc::Rc};
+use lispy::{AST, Interpreter};
+use common::eval;
+
+#[test]
+fn builtins_keep_state_of_their_own() {
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    let mut lisp = Interpreter::new();
+    lisp.register("tick", Rc::new(move |_, args: Vec<AST>| {
+        counter.set(counter.get() + args.len() as i128);
+        Ok(AST::from(counter.get()))
+    }));
+    assert_eq!(eval(&mut lisp, "tick 1 2"), "2");
+    assert_eq!(eval(&mut lisp, "map tick {a b c}"), "{3 4 5}");
+    assert_eq!(calls.get(), 5);
+}
+
+#[test]
+fn hosts_define_and_get_values() {
+    let mut lisp = Interpreter::new();
+    lisp.define("x", AST::from(20));
+    assert_eq!(eval(&mut lisp, "+ x 1"), "21");
+    assert_eq!(lisp.get("x"), Some(AST::from(20)));
+    assert_eq!(lisp.get("y"), None);
+}
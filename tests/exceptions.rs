@@ -0,0 +1,101 @@
+//! Errors caught by `try` as values with a kind, a message and a value.
+
+mod common;
+
+use lispy::{Error, EvalError, Interpreter};
+use common::{error, eval};
+
+fn caught(lisp: &mut Interpreter, body: &str) -> String {
+    let source = format!("try {} (catch {{e}} (list (error-kind e) \
+                          (error-message e)))", body);
+    eval(lisp, &source)
+}
+
+#[test]
+fn every_kind_is_caught() {
+    let mut lisp = Interpreter::new();
+    let kinds = [
+        ("(+ 1 \"a\")", "type", "expected number, got string"),
+        ("(nope 1)", "unbound", "unknown variable: nope"),
+        ("((\\ {x} {x}) 1 2)", "arity", "args mismatch: 1 expected, 2 given"),
+        ("(head {})", "runtime", "qexpr is empty"),
+        ("(error \"bad\")", "user", "bad"),
+        ("(assert (== 1 2))", "assertion", "assertion failed: (== 1 2)"),
+    ];
+    for (body, kind, message) in kinds {
+        assert_eq!(caught(&mut lisp, body),
+                   format!("{{\"{}\" \"{}\"}}", kind, message), "{}", body);
+    }
+    let io = caught(&mut lisp, "(load \"/nonexistent/file.lispy\")");
+    assert!(io.starts_with("{\"io\" \"error reading file: "));
+}
+
+#[test]
+fn any_value_can_be_raised() {
+    let mut lisp = Interpreter::new();
+    let source = "try (error {1 2}) (catch {e} (error-value e))";
+    assert_eq!(eval(&mut lisp, source), "{1 2}");
+    assert_eq!(caught(&mut lisp, "(error 42)"), "{\"user\" \"42\"}");
+}
+
+#[test]
+fn errors_are_reraised_unchanged() {
+    let mut lisp = Interpreter::new();
+    let inner = "(try (+ 1 \"a\") (catch {e} (error e)))";
+    assert_eq!(caught(&mut lisp, inner),
+               "{\"type\" \"expected number, got string\"}");
+    match lisp.eval(&format!("list 1 {}", inner)) {
+        Err(Error::Eval(err)) => {
+            assert_eq!(err.kind(), "type");
+            assert!(err.to_string().ends_with(": expected number, got string"));
+        },
+        other => panic!("error was swallowed: {:?}", other),
+    }
+    let replaced = "(try (+ 1 \"a\") (catch {e} (error \"other\")))";
+    assert_eq!(caught(&mut lisp, replaced), "{\"user\" \"other\"}");
+}
+
+#[test]
+fn exit_is_not_caught() {
+    let mut lisp = Interpreter::new();
+    let result = lisp.eval("try (exit ()) (catch {e} 1)");
+    assert!(matches!(result, Err(Error::Eval(EvalError::Exit))));
+}
+
+#[test]
+fn cleanups_always_run() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {log} (ref {})");
+    assert_eq!(eval(&mut lisp, "try 1 (finally (swap! log join {1}))"), "1");
+    assert!(lisp.eval("try (+ 1 \"a\") (finally (swap! log join {2}))")
+        .is_err());
+    let both = "try (+ 1 \"a\") (catch {e} 3) (finally (swap! log join {3}))";
+    assert_eq!(eval(&mut lisp, both), "3");
+    assert_eq!(eval(&mut lisp, "deref log"), "{1 2 3}");
+}
+
+#[test]
+fn handlers_see_the_scope() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        eval(&mut lisp, "fun {safe x} {try (+ x \"a\") \
+                         (catch {e} (list x (error-kind e)))}");
+        assert_eq!(eval(&mut lisp, "safe 5"), "{5 \"type\"}");
+    }
+}
+
+#[test]
+fn bad_clauses() {
+    let mut lisp = Interpreter::new();
+    let kind = |lisp: &mut Interpreter, source: &str| match lisp.eval(source) {
+        Err(Error::Eval(err)) => err.kind(),
+        other => panic!("{} gave {:?}", source, other),
+    };
+    assert_eq!(kind(&mut lisp, "try 1 (catch {a b} 2)"), "arity");
+    assert_eq!(kind(&mut lisp, "try 1 (catch {e} 2) (catch {e} 3)"), "type");
+    assert_eq!(kind(&mut lisp, "try 1 2"), "type");
+    assert!(error(&mut lisp, "try {+ 1 \"a\"} (\\ {e} {error-kind e})")
+        .ends_with("expected try clause, got S-expr"));
+    assert_eq!(kind(&mut lisp, "finally {1} {2}"), "unbound");
+}
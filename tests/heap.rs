@@ -0,0 +1,135 @@
+//! Cycles through `ref` cells, which reference counting alone never frees.
+
+use lispy::Interpreter;
+
+fn stat(lisp: &mut Interpreter, name: &str) -> String {
+    let stat = format!("get (gc-stats ()) (string->symbol \"{}\")", name);
+    lisp.eval(&stat).unwrap().to_string()
+}
+
+const CYCLES: &str =
+    "map (\\ {i} {do (= {c} (ref i)) (set! c (list c))}) (range 10)";
+
+#[test]
+fn unreachable_cycles_are_freed() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {kept} (ref 0)").unwrap();
+    lisp.eval("set! kept (list kept)").unwrap();
+    lisp.eval(&format!("def {{xs}} ({})", CYCLES)).unwrap();
+    assert_eq!(stat(&mut lisp, "live"), "11");
+    lisp.eval("def {xs} nil").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "collections"), "1");
+    assert_eq!(stat(&mut lisp, "freed"), "10");
+    assert_eq!(stat(&mut lisp, "live"), "1");
+    assert_eq!(lisp.eval("kept").unwrap().to_string(), "<ref {<ref ...>}>");
+}
+
+#[test]
+fn closures_keep_their_cells() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {counter} ((\\ {c} {\\ {_} {swap! c + 1}}) (ref 0))")
+        .unwrap();
+    lisp.eval("counter ()").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(lisp.eval("counter ()").unwrap().to_string(), "2");
+}
+
+#[test]
+fn collections_run_as_cells_are_made() {
+    let mut lisp = Interpreter::new();
+    for _ in 0..150 {
+        lisp.eval(&format!("do ({}) nil", CYCLES)).unwrap();
+    }
+    assert_eq!(stat(&mut lisp, "collections"), "1");
+    assert_eq!(stat(&mut lisp, "live"), stat(&mut lisp, "allocated"));
+}
+
+#[test]
+fn host_values_survive_collections() {
+    let mut lisp = Interpreter::new();
+    let cell = lisp.eval("(\\ {c} {do (set! c (list c)) c}) (ref 5)").unwrap();
+    let counter = lisp.eval("(\\ {c} {\\ {_} {swap! c + 1}}) (ref 0)")
+        .unwrap();
+    let list = lisp.eval("def {a} (ref {})").and_then(|_| {
+        lisp.eval("do (set! a (list (ref 7) a)) (deref a)")
+    }).unwrap();
+    lisp.eval("def {a} nil").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "freed"), "0");
+    assert_eq!(cell.to_string(), "<ref {<ref ...>}>");
+    assert_eq!(list.to_string(), "{<ref 7> <ref {<ref 7> <ref ...>}>}");
+    lisp.define("counter", counter);
+    assert_eq!(lisp.eval("counter ()").unwrap().to_string(), "1");
+    lisp.eval("def {counter} nil").unwrap();
+    drop((cell, list));
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "freed"), "3");
+    assert_eq!(stat(&mut lisp, "live"), "0");
+}
+
+/// Values looked up are the host's too, even lists long enough to share
+/// their storage in chunks.
+#[test]
+fn looked_up_values_survive_collections() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {c} (ref 0)").unwrap();
+    lisp.eval("def {xs} (cons c (range 200))").unwrap();
+    lisp.eval("set! c xs").unwrap();
+    let xs = lisp.get("xs").unwrap();
+    lisp.eval("def {c xs} 0 0").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "freed"), "0");
+    assert!(xs.to_string().starts_with("{<ref {<ref ...> 0 1 2"), "{}", xs);
+}
+
+/// A cycle through many cells, which marking must walk without recursing.
+#[test]
+fn long_chains_of_cells() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {first} (ref nil)").unwrap();
+    lisp.eval("fun {chain n c} {if (== n 0) {c} {chain (- n 1) (ref c)}}")
+        .unwrap();
+    lisp.eval("def {last} (chain 50000 first)").unwrap();
+    lisp.eval("set! first last").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "live"), "50001");
+    lisp.eval("def {first} nil").unwrap();
+    lisp.eval("def {last} nil").unwrap();
+    lisp.eval("gc ()").unwrap();
+    assert_eq!(stat(&mut lisp, "live"), "0");
+}
+
+#[test]
+fn collections_run_while_files_load() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-heap-{}.lispy", std::process::id()));
+    std::fs::write(&file, format!("do ({}) nil\n\
+                                   do (gc ()) ({}) nil\n\
+                                   gc ()\n\
+                                   def {{stats}} (gc-stats ())\n",
+                                  CYCLES.replace("10", "15000"), CYCLES))
+        .unwrap();
+    let mut lisp = Interpreter::new();
+    let loaded = lisp.load(&file);
+    std::fs::remove_file(&file).unwrap();
+    loaded.unwrap();
+    let stats = lisp.eval("stats").unwrap().to_string();
+    assert_eq!(stats, "#{allocated 0 collections 3 freed 15010 live 0}");
+}
+
+/// `gc ()` in the middle of a call keeps what the variables of the call
+/// hold, even a list sharing its storage with a cell nothing else leads to.
+#[test]
+fn calls_keep_their_variables() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        lisp.eval("fun {held d} {do (set! d (list (ref 7) d)) \
+                   (= {xs} (deref d)) (= {d} nil) (gc ()) (nth 0 xs)}")
+            .unwrap();
+        let cell = lisp.eval("held (ref nil)").unwrap();
+        assert_eq!(cell.to_string(), "<ref 7>");
+        assert_eq!(stat(&mut lisp, "freed"), "0");
+    }
+}
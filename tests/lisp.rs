@@ -0,0 +1,30 @@
+//! Runs the `deftest`s of the Lisp test files in this directory, each in a
+//! session of its own.
+
+use lispy::Interpreter;
+
+fn run(file: &str, compile: bool) {
+    let load = || {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        lisp.load(file).unwrap();
+        lisp
+    };
+    let tests = load().tests();
+    assert!(!tests.is_empty(), "{} defines no tests", file);
+    for name in tests {
+        if let Err(err) = load().run_test(&name) {
+            panic!("{}: {}: {}", file, name, err);
+        }
+    }
+}
+
+#[test]
+fn prelude() {
+    run("tests/prelude.lispy", false);
+}
+
+#[test]
+fn prelude_compiled() {
+    run("tests/prelude.lispy", true);
+}
@@ -0,0 +1,174 @@
+//! The native list functions checked against straightforward recursive
+//! definitions in Lisp, written the way the prelude used to write them.
+
+mod common;
+
+use lispy::Interpreter;
+use common::eval;
+
+const REFERENCE: &str = r#"
+fun {lisp-map f l} {fold (\ {x l} {cons (f x) l}) nil l}
+
+fun {lisp-filter p l} {fold (\ {x l} {if (p x) {cons x l} {l}}) nil l}
+
+fun {lisp-reduce f z l} {
+  if (== l nil) {z} {lisp-reduce f (f z (fst l)) (tail l)}
+}
+
+fun {lisp-nth n l} {if (== n 0) {fst l} {lisp-nth (- n 1) (tail l)}}
+
+fun {lisp-last l} {if (== (tail l) nil) {fst l} {lisp-last (tail l)}}
+
+fun {lisp-reverse l} {fold (\ {x acc} {push acc x}) nil l}
+
+fun {lisp-range a b} {if (< a b) {cons a (lisp-range (+ a 1) b)} {nil}}
+
+fun {lisp-take n l} {
+  if (| (== n 0) (== l nil))
+    {nil}
+    {cons (fst l) (lisp-take (- n 1) (tail l))}
+}
+
+fun {lisp-drop n l} {
+  if (| (== n 0) (== l nil)) {l} {lisp-drop (- n 1) (tail l)}
+}
+
+fun {lisp-zip xs ys} {
+  if (| (== xs nil) (== ys nil))
+    {nil}
+    {cons (list (fst xs) (fst ys)) (lisp-zip (tail xs) (tail ys))}
+}
+
+fun {lisp-length l} {fold (\ {_ n} {+ n 1}) 0 l}
+
+fun {lisp-insert x l} {
+  if (== l nil)
+    {list x}
+    {if (< x (fst l))
+      {cons x l}
+      {cons (fst l) (lisp-insert x (tail l))}}
+}
+
+fun {lisp-sort l} {fold lisp-insert nil (lisp-reverse l)}
+"#;
+
+const LISTS: [&str; 5] = [
+    "{}",
+    "{7}",
+    "{3 1 2}",
+    "{5 -2 9 0 9 1/2 4.5}",
+    "(range 40)",
+];
+
+fn interpreter() -> Interpreter {
+    let mut lisp = Interpreter::new();
+    for definition in REFERENCE.split("\n\n") {
+        lisp.eval(definition).unwrap();
+    }
+    lisp
+}
+
+/// Checks that `native` and `reference` agree once `{}` is replaced by each
+/// of the sample lists.
+fn agree(native: &str, reference: &str) {
+    let mut lisp = interpreter();
+    for list in LISTS.iter() {
+        let native = native.replace("{}", list);
+        let reference = reference.replace("{}", list);
+        assert_eq!(
+            eval(&mut lisp, &native),
+            eval(&mut lisp, &reference),
+            "{} and {} differ",
+            native,
+            reference,
+        );
+    }
+}
+
+#[test]
+fn map() {
+    agree("map (\\ {x} {* x 2}) {}", "lisp-map (\\ {x} {* x 2}) {}");
+}
+
+#[test]
+fn filter() {
+    agree("filter (\\ {x} {> x 1}) {}", "lisp-filter (\\ {x} {> x 1}) {}");
+}
+
+#[test]
+fn reduce() {
+    agree("reduce + 0 {}", "lisp-reduce + 0 {}");
+    agree("reduce - 100 {}", "lisp-reduce - 100 {}");
+    agree("reduce (\\ {acc x} {cons x acc}) nil {}",
+          "lisp-reduce (\\ {acc x} {cons x acc}) nil {}");
+}
+
+#[test]
+fn reverse() {
+    agree("reverse {}", "lisp-reverse {}");
+}
+
+#[test]
+fn take_and_drop() {
+    for n in 0..4 {
+        agree(&format!("take {} {{}}", n), &format!("lisp-take {} {{}}", n));
+        agree(&format!("drop {} {{}}", n), &format!("lisp-drop {} {{}}", n));
+    }
+}
+
+#[test]
+fn zip() {
+    agree("zip {} {10 20 30}", "lisp-zip {} {10 20 30}");
+}
+
+#[test]
+fn length() {
+    agree("length {}", "lisp-length {}");
+    agree("len {}", "lisp-length {}");
+}
+
+#[test]
+fn sort() {
+    agree("sort {}", "lisp-sort {}");
+    agree("reverse (sort {} >)", "lisp-sort {}");
+}
+
+#[test]
+fn nth_and_last() {
+    let mut lisp = interpreter();
+    for n in 0..3 {
+        assert_eq!(
+            eval(&mut lisp, &format!("nth {} {{3 1 2}}", n)),
+            eval(&mut lisp, &format!("lisp-nth {} {{3 1 2}}", n)),
+        );
+    }
+    assert_eq!(
+        eval(&mut lisp, "last {3 1 2}"),
+        eval(&mut lisp, "lisp-last {3 1 2}"),
+    );
+    assert!(lisp.eval("nth 3 {3 1 2}").is_err());
+    assert!(lisp.eval("last {}").is_err());
+}
+
+#[test]
+fn range() {
+    for (native, reference) in [
+        ("range 5", "lisp-range 0 5"),
+        ("range 2 5", "lisp-range 2 5"),
+        ("range 5 2", "lisp-range 5 2"),
+    ].iter() {
+        let mut lisp = interpreter();
+        assert_eq!(eval(&mut lisp, native), eval(&mut lisp, reference));
+    }
+    let mut lisp = interpreter();
+    assert_eq!(
+        eval(&mut lisp, "range 5 0 -2"),
+        "{5 3 1}",
+    );
+}
+
+#[test]
+fn do_gives_its_last_argument() {
+    let mut lisp = interpreter();
+    assert_eq!(eval(&mut lisp, "do 1 2 3"), "3");
+}
@@ -0,0 +1,64 @@
+//! `defmacro` and quasiquote templates.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn templates() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "`(1 ,(+ 1 1) ,@{3 4})"), "(1 2 3 4)");
+    assert_eq!(eval(&mut lisp, "`(a ,@{} b)"), "(a b)");
+    assert_eq!(eval(&mut lisp, "`{x ,(* 2 3)}"), "{x 6}");
+    assert!(error(&mut lisp, ",x").ends_with("unquote outside of quasiquote"));
+}
+
+#[test]
+fn arguments_are_not_evaluated() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "defmacro {swap-args f a b} {`(,f ,b ,a)}");
+    assert_eq!(eval(&mut lisp, "swap-args - 1 10"), "9");
+    eval(&mut lisp, "defmacro {twice x} {`(list ,x ,x)}");
+    assert_eq!(eval(&mut lisp, "twice (+ 1 2)"), "{3 3}");
+    eval(&mut lisp, "defmacro {quoted x} {`(list {,x})}");
+    assert_eq!(eval(&mut lisp, "quoted (nope)"), "{{(nope)}}");
+}
+
+#[test]
+fn macros_in_lambda_bodies() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        eval(&mut lisp, "defmacro {twice x} {`(list ,x ,x)}");
+        eval(&mut lisp, "fun {use y} {twice (* y 2)}");
+        assert_eq!(eval(&mut lisp, "use 7"), "{14 14}");
+        assert_eq!(eval(&mut lisp, "list (when #t (+ 1 2)) (unless #t 1)"),
+                   "{3 {}}");
+    }
+}
+
+#[test]
+fn introduced_names_are_fresh() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp,
+         "defmacro {my-or a b} {`(do (= {t#} ,a) (if t# {t#} {,b}))}");
+    eval(&mut lisp, "def {t} 5");
+    assert_eq!(eval(&mut lisp, "my-or #f t"), "5");
+    assert_eq!(eval(&mut lisp, "my-or 1 t"), "1");
+    assert_eq!(eval(&mut lisp, "t"), "5");
+    let x = eval(&mut lisp, "gensym \"x\"");
+    let y = eval(&mut lisp, "gensym \"x\"");
+    assert!(x.starts_with("x#") && y.starts_with("x#") && x != y);
+}
+
+#[test]
+fn failing_expansions() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "defmacro {boom x} {(error \"expansion\")}");
+    assert!(error(&mut lisp, "boom 1").ends_with("exception: expansion"));
+    eval(&mut lisp, "defmacro {splice x} {`(,@x)}");
+    assert!(error(&mut lisp, "splice 1")
+        .ends_with("expected Q-expr, got number"));
+    assert!(error(&mut lisp, "defmacro {} {1}").ends_with("qexpr is empty"));
+}
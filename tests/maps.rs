@@ -0,0 +1,59 @@
+//! Map literals and the functions on maps. Maps are values: `assoc` and
+//! `dissoc` give new maps and leave their argument as it was.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn literals() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "#{a 1 \"b\" 2 3 {x}}"),
+               "#{3 {x} a 1 \"b\" 2}");
+    assert_eq!(eval(&mut lisp, "#{a (+ 1 2)}"), "#{a (+ 1 2)}");
+    assert_eq!(eval(&mut lisp, "#{}"), "#{}");
+    assert!(error(&mut lisp, "#{1 2 3}").starts_with("parse error: 1:1:"));
+}
+
+#[test]
+fn lookups() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {m} #{\"a\" 1 2 \"two\" b 3}");
+    assert_eq!(eval(&mut lisp, "get m \"a\""), "1");
+    assert_eq!(eval(&mut lisp, "get m 2.0"), "\"two\"");
+    assert_eq!(eval(&mut lisp, "get m (string->symbol \"b\")"), "3");
+    assert_eq!(eval(&mut lisp, "get m \"c\""), "{}");
+    assert_eq!(eval(&mut lisp, "get m \"c\" 0"), "0");
+}
+
+#[test]
+fn updates_leave_the_original() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {m} #{\"a\" 1 \"b\" 2}");
+    assert_eq!(eval(&mut lisp, "assoc m \"c\" 3 \"a\" 0"),
+               "#{\"a\" 0 \"b\" 2 \"c\" 3}");
+    assert_eq!(eval(&mut lisp, "dissoc m \"a\" \"z\""), "#{\"b\" 2}");
+    assert_eq!(eval(&mut lisp, "m"), "#{\"a\" 1 \"b\" 2}");
+}
+
+#[test]
+fn keys_values_and_equality() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "keys #{b 1 a 2 1 3 1/2 4}"), "{1/2 1 a b}");
+    assert_eq!(eval(&mut lisp, "vals #{b 1 a 2}"), "{2 1}");
+    assert_eq!(eval(&mut lisp, "== #{a 1 b 2} #{b 2 a 1}"), "#t");
+    assert_eq!(eval(&mut lisp, "== #{a 1} #{a 2}"), "#f");
+}
+
+#[test]
+fn bad_keys_and_arguments() {
+    let mut lisp = Interpreter::new();
+    assert!(error(&mut lisp, "assoc #{} {1} 2")
+        .ends_with("expected map key, got Q-expr"));
+    assert!(error(&mut lisp, "assoc #{} (/ 0.0 0.0) 1")
+        .ends_with("NaN cannot be a map key"));
+    assert!(error(&mut lisp, "get 1 2").ends_with("expected map, got number"));
+    assert!(error(&mut lisp, "assoc #{} 1")
+        .ends_with("args mismatch: 3 expected, 2 given"));
+}
@@ -0,0 +1,104 @@
+//! The numeric tower: exact integers and rationals, inexact floats.
+
+mod common;
+
+use lispy::{AST, Interpreter, Number};
+use common::{error, eval};
+
+#[test]
+fn rationals_stay_exact() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "/ 1 3"), "1/3");
+    assert_eq!(eval(&mut lisp, "+ 1/2 1/3"), "5/6");
+    assert_eq!(eval(&mut lisp, "2/4"), "1/2");
+    assert_eq!(eval(&mut lisp, "- 1/2"), "-1/2");
+    assert!(matches!(lisp.eval("* 2/3 3/2").unwrap(),
+                     AST::Number(Number::Int(1))));
+    assert!(matches!(lisp.eval("/ 6 3").unwrap(),
+                     AST::Number(Number::Int(2))));
+}
+
+#[test]
+fn floats_are_contagious() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "+ 1/2 0.5"), "1.0");
+    assert_eq!(eval(&mut lisp, "* 2 1.5e3"), "3000.0");
+    assert_eq!(eval(&mut lisp, "-0.5"), "-0.5");
+    assert_eq!(eval(&mut lisp, "/ 1.0 0"), "inf");
+}
+
+#[test]
+fn comparisons_across_kinds() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "< 1/3 0.5"), "#t");
+    assert_eq!(eval(&mut lisp, "== 1/2 0.5"), "#t");
+    assert_eq!(eval(&mut lisp, "== 2/4 1/2"), "#t");
+    assert_eq!(eval(&mut lisp, "< 2 3/2"), "#f");
+}
+
+#[test]
+fn rounding_and_conversions() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "floor 7/2"), "3");
+    assert_eq!(eval(&mut lisp, "ceil 7/2"), "4");
+    assert_eq!(eval(&mut lisp, "round 5/2"), "3");
+    assert_eq!(eval(&mut lisp, "truncate -7/2"), "-3");
+    assert_eq!(eval(&mut lisp, "floor -2.5"), "-3.0");
+    assert_eq!(eval(&mut lisp, "exact->inexact 1/4"), "0.25");
+    assert_eq!(eval(&mut lisp, "inexact->exact 0.25"), "1/4");
+    assert!(error(&mut lisp, "inexact->exact (/ 1.0 0)")
+        .ends_with("float is not finite"));
+}
+
+#[test]
+fn division_by_zero() {
+    let mut lisp = Interpreter::new();
+    assert!(error(&mut lisp, "/ 1 0").ends_with("div by zero"));
+    assert!(error(&mut lisp, "/ 1/2 0").ends_with("div by zero"));
+    assert!(error(&mut lisp, "/ 1/2 (- 1/2 1/2)").ends_with("div by zero"));
+}
+
+const MAX: &str = "170141183460469231731687303715884105727";
+const MIN: &str = "-170141183460469231731687303715884105728";
+
+#[test]
+fn overflow_promotes_to_bignums() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, &format!("+ {} 1", MAX)),
+               "170141183460469231731687303715884105728");
+    assert_eq!(eval(&mut lisp, &format!("* {} 2", MAX)),
+               "340282366920938463463374607431768211454");
+    assert_eq!(eval(&mut lisp, &format!("- {} 1", MIN)),
+               "-170141183460469231731687303715884105729");
+    assert_eq!(eval(&mut lisp, &format!("- {}", MIN)),
+               "170141183460469231731687303715884105728");
+    assert_eq!(eval(&mut lisp, "fold * 1 (range 1 31)"),
+               "265252859812191058636308480000000");
+    assert!(matches!(lisp.eval(&format!("+ {} 1", MAX)).unwrap(),
+                     AST::Number(Number::Big(_))));
+}
+
+#[test]
+fn bignums_shrink_back() {
+    let mut lisp = Interpreter::new();
+    let back = format!("- (+ {} 1) 1", MAX);
+    assert_eq!(eval(&mut lisp, &back), MAX);
+    assert!(matches!(lisp.eval(&back).unwrap(), AST::Number(Number::Int(_))));
+    let halved = format!("/ (* {} 4) 2", MAX);
+    assert_eq!(eval(&mut lisp, &halved),
+               "340282366920938463463374607431768211454");
+}
+
+#[test]
+fn bignum_literals_and_mixing() {
+    let mut lisp = Interpreter::new();
+    let big = "99999999999999999999999999999999999999999999";
+    assert_eq!(eval(&mut lisp, big), big);
+    assert_eq!(eval(&mut lisp, &format!("/ {} 3", big)),
+               "33333333333333333333333333333333333333333333");
+    assert_eq!(eval(&mut lisp, &format!("+ 1/2 {}", big)),
+               "199999999999999999999999999999999999999999999/2");
+    assert_eq!(eval(&mut lisp, &format!("+ 0.5 {}", big)), "1e44");
+    assert_eq!(eval(&mut lisp, &format!("< {} {}", MAX, big)), "#t");
+    assert!(error(&mut lisp, &format!("/ {} 0", big)).ends_with("div by zero"));
+}
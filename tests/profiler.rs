@@ -0,0 +1,120 @@
+//! Call counts, times and allocations per function.
+
+use std::rc::Rc;
+use lispy::{
+    AST, CountingAlloc, EnvObj, EvalResult, Interpreter, Profiler, Stats,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+const FIB: &str =
+    "def {fib} (\\ {n} {if (< n 2) {n} {+ (fib (- n 1)) (fib (- n 2))}})";
+
+fn profile(lisp: &mut Interpreter, source: &str) -> Profiler {
+    lisp.eval(FIB).unwrap();
+    lisp.start_profile();
+    lisp.eval(source).unwrap();
+    lisp.finish_profile().unwrap()
+}
+
+fn stats<'a>(profile: &'a Profiler, name: &str) -> &'a Stats {
+    profile.functions().into_iter()
+        .find(|stats| stats.name == name)
+        .unwrap_or_else(|| panic!("{} was not profiled", name))
+}
+
+#[test]
+fn functions_are_counted_and_timed() {
+    let mut lisp = Interpreter::new();
+    let profile = profile(&mut lisp, "fib 10");
+    let fib = stats(&profile, "fib");
+    assert_eq!(fib.calls, 177);
+    assert!(fib.total >= fib.own);
+    assert!(fib.allocations > 0);
+    assert_eq!(stats(&profile, "<").calls, 177);
+    assert_eq!(stats(&profile, "+").calls, 88);
+    assert!(profile.report().starts_with("function"));
+    assert!(lisp.finish_profile().is_none());
+}
+
+#[test]
+fn compiled_lambdas_are_counted() {
+    let mut lisp = Interpreter::new();
+    lisp.compile(true);
+    let profile = profile(&mut lisp, "fib 10");
+    assert_eq!(stats(&profile, "fib").calls, 177);
+}
+
+#[test]
+fn folded_stacks() {
+    let mut lisp = Interpreter::new();
+    let profile = profile(&mut lisp, "fib 3");
+    let folded = profile.folded();
+    let stacks = folded.lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect::<Vec<_>>();
+    assert!(stacks.contains(&"fib;fib;<"));
+    assert!(stacks.contains(&"fib;fib;fib"));
+    assert!(folded.lines()
+        .all(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().is_ok()));
+}
+
+#[test]
+fn profile_builtin() {
+    let mut lisp = Interpreter::new();
+    lisp.eval(FIB).unwrap();
+    let file = std::env::temp_dir()
+        .join(format!("lispy-profile-{}.folded", std::process::id()));
+    let source = format!("profile (fib 5) \"{}\"", file.display());
+    assert_eq!(lisp.eval(&source).unwrap().to_string(), "5");
+    let folded = std::fs::read_to_string(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert!(folded.lines().any(|line| line.starts_with("fib;fib ")));
+    assert_eq!(lisp.eval("profile (profile (fib 6))").unwrap().to_string(),
+               "8");
+    assert!(lisp.finish_profile().is_none());
+}
+
+#[test]
+fn lambdas_are_told_apart_by_their_code() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {add x y} {+ x y}").unwrap();
+    lisp.eval("def {old} add").unwrap();
+    lisp.eval("fun {add x y} {- x y}").unwrap();
+    let profile = profile(&mut lisp, "list (old 1 2) (map (old 1) {1 2})");
+    assert_eq!(stats(&profile, "add").calls, 3);
+    lisp.start_profile();
+    lisp.eval("list (old 1 2) (add 1 2)").unwrap();
+    let profile = lisp.finish_profile().unwrap();
+    let adds = profile.functions().into_iter()
+        .filter(|stats| stats.name == "add")
+        .map(|stats| stats.calls)
+        .collect::<Vec<_>>();
+    assert_eq!(adds, [1, 1]);
+}
+
+#[test]
+fn builtins_are_known_by_their_names() {
+    fn twice(_: EnvObj, args: Vec<AST>) -> EvalResult {
+        Ok(AST::QExpr(args.iter().chain(&args).cloned().collect()))
+    }
+    let mut lisp = Interpreter::new();
+    lisp.register("twice", Rc::new(twice));
+    let source = "list (ref 1) (atom 2) (atom 3) (twice 4)";
+    let profile = profile(&mut lisp, source);
+    assert_eq!(stats(&profile, "ref").calls, 1);
+    assert_eq!(stats(&profile, "atom").calls, 2);
+    assert_eq!(stats(&profile, "twice").calls, 1);
+}
+
+#[test]
+fn sessions_are_profiled_apart() {
+    let (mut profiled, mut other) = (Interpreter::new(), Interpreter::new());
+    profiled.eval(FIB).unwrap();
+    profiled.start_profile();
+    assert!(other.finish_profile().is_none());
+    profiled.eval("fib 4").unwrap();
+    let profile = profiled.finish_profile().unwrap();
+    assert_eq!(stats(&profile, "fib").calls, 9);
+}
@@ -0,0 +1,54 @@
+//! Q-expressions are persistent: operations give new lists that share
+//! their elements with the old ones, which stay as they were.
+
+use lispy::{AST, Interpreter, QExpr};
+
+fn list(xs: &[i128]) -> QExpr {
+    xs.iter().map(|&x| AST::from(x)).collect()
+}
+
+#[test]
+fn operations_leave_the_original() {
+    let xs = list(&[1, 2, 3]);
+    assert_eq!(xs.clone().head().unwrap(), list(&[1]));
+    assert_eq!(xs.clone().tail().unwrap(), list(&[2, 3]));
+    assert_eq!(xs.clone().cons(AST::from(0)), list(&[0, 1, 2, 3]));
+    assert_eq!(xs.clone().join(list(&[4])), list(&[1, 2, 3, 4]));
+    let (left, right) = xs.clone().split_at(1);
+    assert_eq!((left, right), (list(&[1]), list(&[2, 3])));
+    assert_eq!(xs, list(&[1, 2, 3]));
+    assert_eq!(xs.clone().split_at(10).1, list(&[]));
+}
+
+#[test]
+fn empty_lists() {
+    assert!(list(&[]).head().is_err());
+    assert!(list(&[]).tail().is_err());
+    let mut lisp = Interpreter::new();
+    for source in ["head {}", "tail {}"] {
+        let err = lisp.eval(source).unwrap_err().to_string();
+        assert!(err.ends_with("qexpr is empty"), "{}", err);
+    }
+}
+
+#[test]
+fn lists_shared_between_bindings() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {xs} {1 2 3}").unwrap();
+    lisp.eval("def {ys} (cons 0 (tail xs))").unwrap();
+    let eval = |lisp: &mut Interpreter, source| {
+        lisp.eval(source).unwrap().to_string()
+    };
+    assert_eq!(eval(&mut lisp, "list xs ys"), "{{1 2 3} {0 2 3}}");
+    assert_eq!(eval(&mut lisp, "join xs ys {}"), "{1 2 3 0 2 3}");
+}
+
+/// Walking a list with `tail` would take quadratic time if it copied.
+#[test]
+fn walking_long_lists() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {count l n} {if (== l {}) {n} {count (tail l) (+ n 1)}}")
+        .unwrap();
+    let count = lisp.eval("count (range 50000) 0").unwrap();
+    assert_eq!(count.to_string(), "50000");
+}
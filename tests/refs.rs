@@ -0,0 +1,55 @@
+//! Mutable reference cells shared by everything holding them.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn reading_and_writing() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {c} (ref 0)");
+    assert_eq!(eval(&mut lisp, "set! c 5"), "5");
+    assert_eq!(eval(&mut lisp, "deref c"), "5");
+    assert_eq!(eval(&mut lisp, "swap! c + 1 2"), "8");
+    assert_eq!(eval(&mut lisp, "swap! c (\\ {x} {* x 10})"), "80");
+    assert_eq!(eval(&mut lisp, "c"), "<ref 80>");
+    assert_eq!(eval(&mut lisp, "deref (atom {1})"), "{1}");
+}
+
+#[test]
+fn closures_share_cells() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp,
+         "fun {counter _} {(\\ {n} {\\ {_} {swap! n + 1}}) (ref 0)}");
+    eval(&mut lisp, "def {k} (counter ())");
+    eval(&mut lisp, "def {j} (counter ())");
+    assert_eq!(eval(&mut lisp, "list (k ()) (k ()) (j ())"), "{1 2 1}");
+    eval(&mut lisp, "def {log} (ref {})");
+    eval(&mut lisp, "fun {note x} {swap! log join (list x)}");
+    eval(&mut lisp, "map note {1 2 3}");
+    assert_eq!(eval(&mut lisp, "deref log"), "{1 2 3}");
+}
+
+#[test]
+fn identity() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {r} (ref 1)");
+    assert_eq!(eval(&mut lisp, "== r r"), "#t");
+    assert_eq!(eval(&mut lisp, "== (ref 1) (ref 1)"), "#f");
+    eval(&mut lisp, "set! r r");
+    assert_eq!(eval(&mut lisp, "r"), "<ref <ref ...>>");
+}
+
+#[test]
+fn bad_arguments() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "def {c} (ref 0)");
+    assert!(error(&mut lisp, "deref 1").ends_with("expected ref, got number"));
+    assert!(error(&mut lisp, "set! 1 2").ends_with("expected ref, got number"));
+    assert!(error(&mut lisp, "swap! c \"x\"")
+        .ends_with("expected function, got string"));
+    assert!(error(&mut lisp, "swap! c + \"a\"")
+        .ends_with("expected number, got string"));
+    assert_eq!(eval(&mut lisp, "deref c"), "0");
+}
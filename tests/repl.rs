@@ -0,0 +1,92 @@
+//! The `:` commands of the REPL, and how it tells that a form goes on to
+//! the next line.
+
+use lispy::{Command, Interpreter, is_complete};
+
+fn run(lisp: &mut Interpreter, line: &str) -> String {
+    match Command::parse(line) {
+        Some(Ok(command)) => command.run(lisp).unwrap(),
+        other => panic!("{} is not a command: {:?}", line, other),
+    }
+}
+
+#[test]
+fn parsing() {
+    assert_eq!(Command::parse(":env"), Some(Ok(Command::Env)));
+    assert_eq!(Command::parse("  :doc  map "),
+               Some(Ok(Command::Doc("map".into()))));
+    assert_eq!(Command::parse(":time (fib 20)"),
+               Some(Ok(Command::Time("(fib 20)".into()))));
+    assert_eq!(Command::parse(":source"),
+               Some(Err("usage: :source symbol".into())));
+    assert_eq!(Command::parse(":quit now"), Some(Err("usage: :quit".into())));
+    assert_eq!(Command::parse(":break sq"), None);
+    assert_eq!(Command::parse("+ 1 2"), None);
+}
+
+#[test]
+fn inspecting_definitions() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("def {answer} 42").unwrap();
+    lisp.eval("fun {twice f x} {f (f x)}").unwrap();
+    let env = run(&mut lisp, ":env");
+    let bound = |name, kind| env.lines()
+        .any(|line| line == format!("{:<20} {}", name, kind));
+    assert!(bound("answer", "number"));
+    assert!(bound("twice", "lambda {f x}"));
+    assert!(bound("and", "special form"));
+    assert_eq!(run(&mut lisp, ":doc answer"), "answer: number");
+    assert_eq!(run(&mut lisp, ":doc twice"), "(twice f x)");
+    assert_eq!(run(&mut lisp, ":doc +"), "+: builtin function");
+    assert_eq!(run(&mut lisp, ":source twice"), "(\\ {f x} {f (f x)})");
+    lisp.eval("fun {tagged tag & xs} {list tag xs}").unwrap();
+    assert_eq!(run(&mut lisp, ":source tagged"),
+               "(\\ {tag & xs} {list tag xs})");
+    assert_eq!(run(&mut lisp, ":type twice"), "lambda {f x}");
+    assert_eq!(run(&mut lisp, ":type (list 1 2)"), "Q-expr");
+    let time = run(&mut lisp, ":time twice (\\ {x} {* x 2}) 3");
+    assert!(time.starts_with("12\ntime: "));
+    assert!(Command::Doc("nothing".into()).run(&mut lisp).is_err());
+}
+
+#[test]
+fn docs_come_from_comments() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-repl-{}.lispy", std::process::id()));
+    std::fs::write(&file, "(def {x} 1)\n; Squares a number.\n;; Really.\n\
+                           (def {sq}\n  (\\ {x} {* x x}))\n").unwrap();
+    let mut lisp = Interpreter::new();
+    let loaded = run(&mut lisp, &format!(":load {}", file.display()));
+    assert_eq!(loaded, format!("loaded {}", file.display()));
+    std::fs::write(&file, "").unwrap();
+    let doc = run(&mut lisp, ":doc sq");
+    std::fs::remove_file(&file).unwrap();
+    let doc = doc.lines().collect::<Vec<_>>();
+    assert_eq!(doc[..3], ["(sq x)", "Squares a number.", "Really."]);
+    assert!(doc[3].starts_with("defined at "));
+    let doc = run(&mut lisp, ":doc flip");
+    assert!(doc.starts_with("(flip f a b)\ndefined at prelude.lispy:"));
+}
+
+#[test]
+fn reset_keeps_the_prelude() {
+    let mut lisp = Interpreter::new();
+    lisp.compile(true);
+    lisp.eval("def {answer} 42").unwrap();
+    run(&mut lisp, ":reset");
+    assert!(lisp.get("answer").is_none());
+    assert_eq!(lisp.eval("sum {1 2 3}").unwrap().to_string(), "6");
+    assert!(lisp.compiling());
+}
+
+#[test]
+fn forms_go_on_while_brackets_are_open() {
+    for complete in ["", "+ 1 2", "(+ 1 2)", "{1 {2}}", "\"(\"", "\"\\\"{\"",
+                     "1 ; (", "; {\n2", "(+ 1 2))"] {
+        assert!(is_complete(complete), "{}", complete);
+    }
+    for open in ["(+ 1", "{1 {2}", "(list {1", "\"abc", "\"\\\"", "(\")\"",
+                 "{; }\n"] {
+        assert!(!is_complete(open), "{}", open);
+    }
+}
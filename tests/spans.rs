@@ -0,0 +1,84 @@
+//! Evaluation and parse errors tell where the failing expression was read.
+
+mod common;
+
+use std::{fs, process};
+use lispy::Interpreter;
+use common::error;
+
+#[test]
+fn symbols_in_loaded_files() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-spans-{}.lispy", process::id()));
+    fs::write(&file, "def {x} 1\n\nfun {f y} {\n  + y\n    missing}\n\n\
+                      fun {g y} {list y (+ y \"a\")}\n").unwrap();
+    let mut lisp = Interpreter::new();
+    let loaded = lisp.load(&file);
+    fs::remove_file(&file).unwrap();
+    loaded.unwrap();
+    let file = file.display();
+    let err = error(&mut lisp, "f 1");
+    assert!(err.contains(&format!("(f 1) defined at {}:3:11", file)));
+    assert!(err.ends_with(&format!("{}:5:5: unknown variable: missing",
+                                   file)));
+    let err = error(&mut lisp, "g 1");
+    assert!(err.ends_with(&format!("{}:7:19: expected number, got string",
+                                   file)));
+}
+
+#[test]
+fn loading_stops_at_the_first_error() {
+    let file = std::env::temp_dir()
+        .join(format!("lispy-spans-stop-{}.lispy", process::id()));
+    let mut lisp = Interpreter::new();
+    for failing in ["(+ 1 \"a\")", "(+ 1 ]"] {
+        fs::write(&file, format!("def {{a}} 1\n{}\ndef {{b}} 2\n", failing))
+            .unwrap();
+        let err = lisp.load(&file).unwrap_err().to_string();
+        assert!(err.contains(&format!("{}:2:1", file.display())), "{}", err);
+        assert_eq!(lisp.eval("a").unwrap().to_string(), "1");
+        assert!(lisp.get("b").is_none());
+    }
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn symbols_in_input() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(error(&mut lisp, "+ 1 nope"),
+               "eval error: 1:5: unknown variable: nope");
+    assert_eq!(error(&mut lisp, "list 1\n  (+ 2 nope)"),
+               "eval error: 2:8: unknown variable: nope");
+    assert_eq!(error(&mut lisp, "(nope)"),
+               "eval error: 1:2: unknown variable: nope");
+    assert_eq!(error(&mut lisp, "list 1 (nope 2)"),
+               "eval error: 1:9: unknown variable: nope");
+}
+
+#[test]
+fn symbols_in_compiled_lambdas() {
+    let mut lisp = Interpreter::new();
+    lisp.compile(true);
+    lisp.eval("fun {f y} {+ y\n  missing}").unwrap();
+    assert!(error(&mut lisp, "f 1")
+        .ends_with("2:3: unknown variable: missing"));
+}
+
+#[test]
+fn failing_calls() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(error(&mut lisp, "list 1 (+ 2 \"a\")"),
+               "eval error: 1:8: expected number, got string");
+    assert_eq!(error(&mut lisp, "+ 1 2 \"a\""),
+               "eval error: 1:1: expected number, got string");
+}
+
+#[test]
+fn parse_errors() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(error(&mut lisp, "+ 1 ]"), "parse error: 1:5: cannot parse `]`");
+    assert_eq!(error(&mut lisp, "(+ 1\n  2"),
+               "parse error: 1:1: cannot parse `(+ 1`");
+    assert_eq!(error(&mut lisp, "list {1 2"),
+               "parse error: 1:6: cannot parse `{1 2`");
+}
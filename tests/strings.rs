@@ -0,0 +1,64 @@
+//! The string library. Lengths and indices count characters, not bytes.
+
+mod common;
+
+use lispy::Interpreter;
+use common::{error, eval};
+
+#[test]
+fn building_and_measuring() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "string-append \"ab\" \"cd\" \"\""), "\"abcd\"");
+    assert_eq!(eval(&mut lisp, "string-length \"héllo\""), "5");
+    assert_eq!(eval(&mut lisp, "string-length \"a\\\"b\""), "3");
+    assert_eq!(eval(&mut lisp, "\"tab\\there\""), "\"tab\\there\"");
+}
+
+#[test]
+fn slicing_and_searching() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "substring \"hello\" 1 3"), "\"el\"");
+    assert_eq!(eval(&mut lisp, "substring \"héllo\" 1"), "\"éllo\"");
+    assert_eq!(eval(&mut lisp, "string-index \"héllo\" \"l\""), "2");
+    assert_eq!(eval(&mut lisp, "string-index \"hello\" \"z\""), "-1");
+    assert_eq!(eval(&mut lisp, "string-split \"a,b,,c\" \",\""),
+               "{\"a\" \"b\" \"\" \"c\"}");
+    assert_eq!(eval(&mut lisp, "string-split \"abc\" \"\""),
+               "{\"a\" \"b\" \"c\"}");
+}
+
+#[test]
+fn case_and_whitespace() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "string-upcase \"abé\""), "\"ABÉ\"");
+    assert_eq!(eval(&mut lisp, "string-downcase \"ABC\""), "\"abc\"");
+    assert_eq!(eval(&mut lisp, "string-trim \" \\t x y \\n\""), "\"x y\"");
+}
+
+#[test]
+fn conversions() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "string->number \"1/2\""), "1/2");
+    assert_eq!(eval(&mut lisp, "string->number \"-0.5e1\""), "-5.0");
+    assert_eq!(eval(&mut lisp, "number->string 0.5"), "\"0.5\"");
+    assert_eq!(eval(&mut lisp, "string->symbol \"foo\""), "foo");
+    assert_eq!(eval(&mut lisp, "symbol->string (string->symbol \"x\")"),
+               "\"x\"");
+}
+
+#[test]
+fn bad_arguments() {
+    let mut lisp = Interpreter::new();
+    assert!(error(&mut lisp, "substring \"hello\" 3 10")
+        .ends_with("substring out of range"));
+    assert!(error(&mut lisp, "substring \"hello\" 3 1")
+        .ends_with("substring out of range"));
+    assert!(error(&mut lisp, "substring \"hello\" -1")
+        .ends_with("substring out of range"));
+    assert!(error(&mut lisp, "string->number \"abc\"")
+        .ends_with("not a number"));
+    assert!(error(&mut lisp, "string-append \"a\" 1")
+        .ends_with("expected string, got number"));
+    assert!(error(&mut lisp, "symbol->string \"x\"")
+        .ends_with("expected symbol, got string"));
+}
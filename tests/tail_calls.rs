@@ -0,0 +1,69 @@
+//! Calls in tail position run in constant stack space, so loops written as
+//! recursion do not overflow however long they run.
+
+mod common;
+
+use lispy::Interpreter;
+use common::eval;
+
+const DEPTH: usize = 50_000;
+
+#[test]
+fn self_recursion() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp,
+         "fun {sum-to n acc} {if (== n 0) {acc} {sum-to (- n 1) (+ acc n)}}");
+    assert_eq!(eval(&mut lisp, &format!("sum-to {} 0", DEPTH)), "1250025000");
+}
+
+#[test]
+fn mutual_recursion() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {even n} {if (== n 0) {#t} {odd (- n 1)}}");
+    eval(&mut lisp, "fun {odd n} {if (== n 0) {#f} {even (- n 1)}}");
+    assert_eq!(eval(&mut lisp, &format!("even {}", DEPTH + 1)), "#f");
+}
+
+#[test]
+fn tail_positions() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {ev n} {if (== n 0) {0} {eval {ev (- n 1)}}}");
+    eval(&mut lisp,
+         "fun {cnd n} {cond {(== n 0) \"cond\"} {else (cnd (- n 1))}}");
+    eval(&mut lisp, "fun {wh n} {when (> n 0) (wh (- n 1))}");
+    assert_eq!(eval(&mut lisp, &format!("ev {}", DEPTH)), "0");
+    assert_eq!(eval(&mut lisp, &format!("cnd {}", DEPTH)), "\"cond\"");
+    assert_eq!(eval(&mut lisp, &format!("wh {}", DEPTH)), "{}");
+}
+
+#[test]
+fn errors_at_the_bottom() {
+    let mut lisp = Interpreter::new();
+    eval(&mut lisp, "fun {down n} {if (== n 0) {(+ 1 \"a\")} {down (- n 1)}}");
+    let err = lisp.eval(&format!("down {}", DEPTH)).unwrap_err();
+    assert!(err.to_string().ends_with("expected number, got string"));
+}
+
+#[test]
+fn folding_long_lists() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(eval(&mut lisp, "fold + 0 (range 100000)"), "4999950000");
+    assert_eq!(eval(&mut lisp, "len (fold cons {} (range 100000))"),
+               "100000");
+}
+
+/// Calls that are not in tail position nest, up to a limit that gives an
+/// error rather than overflowing the stack of the thread.
+#[test]
+fn deep_recursion() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        eval(&mut lisp,
+             "fun {depth n} {if (== n 0) {0} {+ 1 (depth (- n 1))}}");
+        assert_eq!(eval(&mut lisp, "depth 5000"), "5000");
+        let err = lisp.eval("depth 1000000").unwrap_err().to_string();
+        assert!(err.ends_with("calls nest too deeply"), "{}", err);
+        assert_eq!(eval(&mut lisp, "depth 5000"), "5000");
+    }
+}
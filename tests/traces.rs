@@ -0,0 +1,76 @@
+//! Errors escaping lambda calls carry a backtrace of those calls.
+
+use lispy::{Error, EvalError, Interpreter};
+
+fn trace(lisp: &mut Interpreter, source: &str) -> String {
+    match lisp.eval(source) {
+        Ok(value) => panic!("{} gave {}", source, value),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[test]
+fn frames_name_the_calls() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {inner x} {+ x \"a\"}").unwrap();
+    lisp.eval("fun {outer x y} {list (inner x) y}").unwrap();
+    assert_eq!(trace(&mut lisp, "outer 1 {2 3}"),
+               "eval error: traceback (most recent call last):\n\
+                \x20 (outer 1 {2 3}) defined at 1:17\n\
+                \x20 (inner 1) defined at 1:15\n\
+                1:15: expected number, got string");
+    let lambda = trace(&mut lisp, "(\\ {x} {list (inner x)}) 5");
+    assert!(lambda.contains("\n  (<lambda> 5) defined at 1:8\n"));
+}
+
+#[test]
+fn variadic_frames() {
+    for compile in [false, true] {
+        let mut lisp = Interpreter::new();
+        lisp.compile(compile);
+        lisp.eval("fun {sum x & rest} {+ x (head rest)}").unwrap();
+        assert_eq!(trace(&mut lisp, "sum 1 \"a\" 3"),
+                   "eval error: traceback (most recent call last):\n\
+                    \x20 (sum 1 \"a\" 3) defined at 1:20\n\
+                    1:20: expected number, got Q-expr");
+    }
+}
+
+#[test]
+fn frames_as_values() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {inner x} {+ x \"a\"}").unwrap();
+    lisp.eval("fun {outer x} {list (inner (* x 2))}").unwrap();
+    let (frames, err) = match lisp.eval("outer 3") {
+        Err(Error::Eval(EvalError::Trace(frames, err))) => (frames, err),
+        other => panic!("no backtrace: {:?}", other),
+    };
+    let calls = frames.iter()
+        .map(|frame| (frame.name.as_deref(), frame.args[0].to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(calls, [(Some("inner"), "6".to_string()),
+                       (Some("outer"), "3".to_string())]);
+    assert_eq!(err.kind(), "type");
+}
+
+#[test]
+fn long_traces_are_cut() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {deep n} {if (== n 0) {(+ 1 \"a\")} {list (deep (- n 1))}}")
+        .unwrap();
+    let trace = trace(&mut lisp, "deep 20");
+    let lines = trace.lines().collect::<Vec<_>>();
+    assert_eq!(lines[1], "  ... 5 more");
+    assert_eq!(lines[2], "  (deep 15) defined at 1:14");
+    assert_eq!(lines[17], "  (deep 0) defined at 1:14");
+    assert_eq!(lines.len(), 19);
+}
+
+#[test]
+fn caught_errors_drop_the_trace() {
+    let mut lisp = Interpreter::new();
+    lisp.eval("fun {inner x} {+ x \"a\"}").unwrap();
+    let caught = "try {inner 1} (catch {e} (error-message e))";
+    assert_eq!(lisp.eval(caught).unwrap().to_string(),
+               "\"expected number, got string\"");
+}